@@ -0,0 +1,576 @@
+//! Creates and edits the encrypted config (`CFG`) of the ESP32 TOTP gadget.
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use clap::{Args, Parser, Subcommand};
+use data_encoding::BASE32_NOPAD;
+use esp_totp_core::algorithm::Trigger;
+use esp_totp_core::import::{self, aegis, andotp, bitwarden, twofas, Report};
+use esp_totp_core::software::Software;
+use esp_totp_core::vault::{self, Cipher, Config, Kdf, Token};
+use esp_totp_core::Algorithm;
+use esp_totp_core::{migration, otpauth};
+use image::Luma;
+use qrcode::render::{svg, unicode};
+use qrcode::QrCode;
+
+/// Minimum width and height of a QR code image in pixels.
+const QR_SIZE: u32 = 256;
+
+/// Iterations of the key derivation of new configs, takes about a second on the device.
+const DEFAULT_ITERATIONS: u32 = 10000;
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    /// The config, copied to the SD card as `CFG`.
+    #[arg(short, long, default_value = "CFG")]
+    file: PathBuf,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Creates an empty config.
+    Init(Encryption),
+    /// Adds a token.
+    Add(TokenArgs),
+    /// Adds a token from an `otpauth://` URI, e.g. read from the QR code of the service.
+    AddUri {
+        uri: String,
+        /// Name shown on the device [default: the account of the URI]
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// Adds the tokens of a Google Authenticator export, the `otpauth-migration://` URIs of all
+    /// its QR codes.
+    ImportGoogle {
+        #[arg(required = true)]
+        uris: Vec<String>,
+    },
+    /// Adds the tokens of an Aegis export, asks for its password if it is encrypted.
+    ImportAegis { file: PathBuf },
+    /// Adds the tokens of an unencrypted Bitwarden JSON export.
+    ImportBitwarden { file: PathBuf },
+    /// Adds the tokens of a plaintext andOTP backup.
+    #[command(name = "import-andotp")]
+    ImportAndOtp { file: PathBuf },
+    /// Adds the tokens of an unencrypted 2FAS backup.
+    #[command(name = "import-2fas")]
+    ImportTwoFas { file: PathBuf },
+    /// Removes a token.
+    Remove { name: String },
+    /// Renames a token.
+    Rename { name: String, new_name: String },
+    /// Lists the tokens, no PIN needed.
+    List,
+    /// Encrypts the keys with a new PIN.
+    Rekey(Encryption),
+    /// Prints the current codes, to compare them with the device.
+    Codes,
+    /// Prints the tokens as otpauth URIs, e.g. to enroll a phone as backup. The URIs contain the
+    /// secrets.
+    Export(ExportArgs),
+}
+
+#[derive(Args)]
+struct Encryption {
+    /// `aes-128-ctr` or `aes-128-gcm` (a wrong PIN is detected) [default: aes-128-ctr, keeps the
+    /// cipher of an existing config]
+    #[arg(long, value_parser = parse_cipher)]
+    cipher: Option<Cipher>,
+    /// Iterations of the PBKDF2 key derivation, 0 uses the PIN as key [default: 10000, keeps the
+    /// iterations of an existing config]
+    #[arg(long)]
+    iterations: Option<u32>,
+}
+
+#[derive(Args)]
+struct ExportArgs {
+    /// Google Authenticator migration URIs instead of one otpauth URI per token, tokens it does
+    /// not support are skipped.
+    #[arg(long)]
+    migration: bool,
+    /// Shows the QR code of every URI in the terminal.
+    #[arg(long)]
+    qr: bool,
+    /// Writes the QR code of every URI to this directory, as PNG.
+    #[arg(long)]
+    qr_dir: Option<PathBuf>,
+    /// Writes the QR codes as SVG instead.
+    #[arg(long, requires = "qr_dir")]
+    svg: bool,
+}
+
+/// Fields of a new token, see the README for their meaning.
+#[derive(Args)]
+struct TokenArgs {
+    name: String,
+    /// Base32 encoded secret, as shown by the service.
+    secret: String,
+    #[arg(long = "type", default_value = "totp")]
+    kind: String,
+    #[arg(long)]
+    period: Option<u64>,
+    #[arg(long)]
+    digits: Option<u8>,
+    /// `SHA1`, `SHA256` or `SHA512`.
+    #[arg(long, value_parser = parse_algorithm)]
+    algorithm: Option<Algorithm>,
+    #[arg(long)]
+    counter: Option<u64>,
+    #[arg(long)]
+    pin_length: Option<u8>,
+    #[arg(long)]
+    suite: Option<String>,
+    #[arg(long)]
+    t0: Option<u64>,
+    #[arg(long, allow_hyphen_values = true)]
+    offset_seconds: Option<i64>,
+    #[arg(long)]
+    verify_window: Option<u8>,
+}
+
+#[derive(Debug)]
+enum Error {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Vault(vault::Error),
+    Uri(otpauth::Error),
+    Migration(migration::Error),
+    Aegis(aegis::Error),
+    Import(import::Error),
+    Qr(qrcode::types::QrError),
+    Image(image::ImageError),
+    /// The secret is not base32, only spaces, padding and lowercase letters are tolerated.
+    InvalidSecret,
+    /// The PIN has to consist of [`vault::PIN_LENGTH`] digits, it is entered on the device.
+    InvalidPin,
+    PinMismatch,
+    Exists(PathBuf),
+    UnknownToken(String),
+    DuplicateToken(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{}", err),
+            Error::Json(err) => write!(f, "invalid JSON: {}", err),
+            Error::Vault(err) => write!(f, "invalid config: {:?}", err),
+            Error::Uri(err) => write!(f, "invalid otpauth URI: {:?}", err),
+            Error::Migration(err) => write!(f, "invalid Google Authenticator export: {:?}", err),
+            Error::Aegis(err) => write!(f, "invalid Aegis export: {:?}", err),
+            Error::Import(err) => write!(f, "invalid export: {:?}", err),
+            Error::Qr(err) => write!(f, "could not create the QR code: {}", err),
+            Error::Image(err) => write!(f, "could not write the QR code: {}", err),
+            Error::InvalidSecret => write!(f, "invalid base32 secret"),
+            Error::InvalidPin => write!(f, "the PIN has to have {} digits", vault::PIN_LENGTH),
+            Error::PinMismatch => write!(f, "the PINs do not match"),
+            Error::Exists(path) => write!(f, "{} already exists", path.display()),
+            Error::UnknownToken(name) => write!(f, "there is no token {}", name),
+            Error::DuplicateToken(name) => write!(f, "there already is a token {}", name),
+        }
+    }
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// The names of the config, e.g. `aes-128-gcm`.
+fn parse_cipher(cipher: &str) -> std::result::Result<Cipher, String> {
+    serde_json::from_value(cipher.into()).map_err(|err| err.to_string())
+}
+
+/// The names of the config, e.g. `SHA256`.
+fn parse_algorithm(algorithm: &str) -> std::result::Result<Algorithm, String> {
+    serde_json::from_value(algorithm.to_uppercase().into()).map_err(|err| err.to_string())
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Command::Init(encryption) => init(&cli.file, encryption),
+        Command::Add(token) => add(&cli.file, token),
+        Command::AddUri { uri, name } => add_uri(&cli.file, &uri, name),
+        Command::ImportGoogle { uris } => import_google(&cli.file, &uris),
+        Command::ImportAegis { file } => import_aegis(&cli.file, &file),
+        Command::ImportBitwarden { file } => import_file(&cli.file, &file, bitwarden::import),
+        Command::ImportAndOtp { file } => import_file(&cli.file, &file, andotp::import),
+        Command::ImportTwoFas { file } => import_file(&cli.file, &file, twofas::import),
+        Command::Remove { name } => remove(&cli.file, &name),
+        Command::Rename { name, new_name } => rename(&cli.file, &name, new_name),
+        Command::List => list(&cli.file),
+        Command::Rekey(encryption) => rekey(&cli.file, encryption),
+        Command::Codes => codes(&cli.file),
+        Command::Export(args) => export(&cli.file, args),
+    };
+    match result {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn init(path: &Path, encryption: Encryption) -> Result<()> {
+    if path.exists() {
+        return Err(Error::Exists(path.to_path_buf()));
+    }
+    let iterations = encryption.iterations.unwrap_or(DEFAULT_ITERATIONS);
+    let mut config = Config::new(encryption.cipher.unwrap_or_default(), kdf(iterations));
+    let pin = read_pin("New PIN: ", true)?;
+    config
+        .encrypt(&mut Software, &pin, &mut random)
+        .map_err(Error::Vault)?;
+    save(path, &config)
+}
+
+fn add(path: &Path, args: TokenArgs) -> Result<()> {
+    let mut token = Token::new(args.name, decode_secret(&args.secret)?);
+    token.kind = args.kind;
+    token.period = args.period.unwrap_or(token.period);
+    token.digits = args.digits.unwrap_or(token.digits);
+    token.algorithm = args.algorithm.unwrap_or(token.algorithm);
+    token.counter = args.counter.unwrap_or(token.counter);
+    token.pin_length = args.pin_length.unwrap_or(token.pin_length);
+    token.suite = args.suite;
+    token.t0 = args.t0.unwrap_or(token.t0);
+    token.offset_seconds = args.offset_seconds.unwrap_or(token.offset_seconds);
+    token.verify_window = args.verify_window.unwrap_or(token.verify_window);
+    push(path, vec![token])
+}
+
+fn add_uri(path: &Path, uri: &str, name: Option<String>) -> Result<()> {
+    let mut token = otpauth::parse(uri).map_err(Error::Uri)?;
+    token.secret = decode_secret(&token.key)?;
+    token.name = name.unwrap_or(token.name);
+    push(path, vec![token])
+}
+
+fn import_google(path: &Path, uris: &[String]) -> Result<()> {
+    let tokens =
+        migration::parse_batch(uris.iter().map(String::as_str)).map_err(Error::Migration)?;
+    import(
+        path,
+        Report {
+            tokens,
+            ..Report::default()
+        },
+    )
+}
+
+fn import_aegis(path: &Path, file: &Path) -> Result<()> {
+    let backup = aegis::Backup::parse(&fs::read(file).map_err(Error::Io)?).map_err(Error::Aegis)?;
+    let password = match backup.is_encrypted() {
+        true => Some(rpassword::prompt_password("Aegis password: ").map_err(Error::Io)?),
+        false => None,
+    };
+    let report = backup.import(password.as_deref()).map_err(Error::Aegis)?;
+    import(path, report)
+}
+
+fn import_file(
+    path: &Path,
+    file: &Path,
+    parse: fn(&[u8]) -> std::result::Result<Report, import::Error>,
+) -> Result<()> {
+    let report = parse(&fs::read(file).map_err(Error::Io)?).map_err(Error::Import)?;
+    import(path, report)
+}
+
+/// Adds the imported tokens, their `key` is the base32 encoded secret. The skipped entries are
+/// listed.
+fn import(path: &Path, report: Report) -> Result<()> {
+    for (label, reason) in report.skipped.iter() {
+        println!("Skipped {} ({:?})", label, reason);
+    }
+    let mut tokens = report.tokens;
+    // the account alone is often not unique, e.g. the same mail address at several services
+    let config = load(path)?;
+    for i in 0..tokens.len() {
+        let name = &tokens[i].name;
+        let existing = config.tokens.iter().chain(&tokens[..i]);
+        let taken = existing.map(|other| &other.name).any(|other| other == name);
+        if let (true, Some(issuer)) = (taken, &tokens[i].issuer) {
+            tokens[i].name = format!("{}: {}", issuer, name);
+        }
+    }
+    for token in tokens.iter_mut() {
+        token.secret = decode_secret(&token.key)?;
+    }
+    let count = tokens.len();
+    push(path, tokens)?;
+    println!("Imported {} tokens", count);
+    Ok(())
+}
+
+/// Adds the tokens with their decoded `secret` to the config.
+fn push(path: &Path, tokens: Vec<Token>) -> Result<()> {
+    let mut config = load(path)?;
+    for (i, token) in tokens.iter().enumerate() {
+        let existing = config.tokens.iter().chain(&tokens[..i]);
+        if existing
+            .map(|other| &other.name)
+            .any(|name| *name == token.name)
+        {
+            return Err(Error::DuplicateToken(token.name.clone()));
+        }
+    }
+    let pin = unlock(&mut config, true)?;
+    config.tokens.extend(tokens);
+    config
+        .encrypt(&mut Software, &pin, &mut random)
+        .map_err(Error::Vault)?;
+    save(path, &config)
+}
+
+fn remove(path: &Path, name: &str) -> Result<()> {
+    let mut config = load(path)?;
+    let index = find(&config, name)?;
+    // the keys are encrypted again, with AES-CTR they depend on the position of the token
+    let pin = unlock(&mut config, true)?;
+    config.tokens.remove(index);
+    config
+        .encrypt(&mut Software, &pin, &mut random)
+        .map_err(Error::Vault)?;
+    save(path, &config)
+}
+
+fn rename(path: &Path, name: &str, new_name: String) -> Result<()> {
+    let mut config = load(path)?;
+    let index = find(&config, name)?;
+    if config.tokens.iter().any(|token| token.name == new_name) {
+        return Err(Error::DuplicateToken(new_name));
+    }
+    config.tokens[index].name = new_name;
+    save(path, &config)
+}
+
+fn list(path: &Path) -> Result<()> {
+    let config = load(path)?;
+    for token in config.tokens.iter() {
+        let details = match token.otp().trigger() {
+            Trigger::Time => format!("{} digits, {}s", token.digits, token.time_step()),
+            Trigger::Counter => format!("{} digits, counter {}", token.digits, token.counter),
+            Trigger::Challenge => token.suite.clone().unwrap_or_default(),
+        };
+        let issuer = token.issuer.as_deref().unwrap_or_default();
+        println!(
+            "{:<16} {:<16} {:<6} {}",
+            token.name, issuer, token.kind, details
+        );
+    }
+    Ok(())
+}
+
+fn rekey(path: &Path, encryption: Encryption) -> Result<()> {
+    let mut config = load(path)?;
+    unlock(&mut config, true)?;
+    config.cipher = encryption.cipher.unwrap_or(config.cipher);
+    let iterations = encryption
+        .iterations
+        .or(config.kdf.as_ref().map(|kdf| kdf.iterations));
+    // always with a new salt
+    config.kdf = iterations.and_then(kdf);
+    let pin = read_pin("New PIN: ", true)?;
+    config
+        .encrypt(&mut Software, &pin, &mut random)
+        .map_err(Error::Vault)?;
+    save(path, &config)
+}
+
+fn codes(path: &Path) -> Result<()> {
+    let mut config = load(path)?;
+    unlock(&mut config, false)?;
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time before 1970")
+        .as_secs();
+    for token in config.tokens.iter_mut() {
+        if token.needs_pin() {
+            let pin = rpassword::prompt_password(format!("PIN of {}: ", token.name))
+                .map_err(Error::Io)?;
+            token.pin = Some(pin);
+        }
+        token.prepare(&mut Software);
+        let code = match token.otp().trigger() {
+            Trigger::Time => match token.code(&mut Software, timestamp, "") {
+                Ok(code) => format!("{} (valid for {}s)", code, code.valid_until - timestamp),
+                Err(err) => format!("{:?}", err),
+            },
+            Trigger::Counter => match token.code(&mut Software, timestamp, "") {
+                Ok(code) => format!("{} (counter {})", code, token.counter),
+                Err(err) => format!("{:?}", err),
+            },
+            Trigger::Challenge => "challenge-response".into(),
+        };
+        println!("{:<16} {}", token.name, code);
+    }
+    Ok(())
+}
+
+fn export(path: &Path, args: ExportArgs) -> Result<()> {
+    let mut config = load(path)?;
+    unlock(&mut config, false)?;
+
+    // file names of the QR codes and the URIs
+    let uris: Vec<(String, String)> = if args.migration {
+        let mut supported = Vec::new();
+        for token in config.tokens.iter() {
+            match migration::check(token) {
+                Ok(()) => supported.push(token),
+                Err(err) => eprintln!("Skipped {} ({:?})", token.name, err),
+            }
+        }
+        let mut batch_id = [0u8; 4];
+        random(&mut batch_id);
+        let uris = migration::export(&supported, i32::from_le_bytes(batch_id));
+        let count = uris.len();
+        uris.into_iter()
+            .enumerate()
+            .map(|(i, uri)| (format!("migration-{}-of-{}", i + 1, count), uri))
+            .collect()
+    } else {
+        let mut uris = Vec::new();
+        for (i, token) in config.tokens.iter().enumerate() {
+            match otpauth::to_uri(token) {
+                Ok(uri) => uris.push((file_name(i, &token.name), uri)),
+                Err(err) => eprintln!("Skipped {} ({:?})", token.name, err),
+            }
+        }
+        uris
+    };
+
+    if let Some(dir) = &args.qr_dir {
+        fs::create_dir_all(dir).map_err(Error::Io)?;
+    }
+    for (name, uri) in uris.iter() {
+        println!("{}", uri);
+        if !args.qr && args.qr_dir.is_none() {
+            continue;
+        }
+        let code = QrCode::new(uri.as_bytes()).map_err(Error::Qr)?;
+        if args.qr {
+            // light modules as dark characters, for terminals with a dark background
+            let image = code
+                .render::<unicode::Dense1x2>()
+                .dark_color(unicode::Dense1x2::Light)
+                .light_color(unicode::Dense1x2::Dark)
+                .build();
+            println!("{}", image);
+        }
+        match &args.qr_dir {
+            Some(dir) if args.svg => {
+                let image = code
+                    .render::<svg::Color>()
+                    .min_dimensions(QR_SIZE, QR_SIZE)
+                    .build();
+                fs::write(dir.join(format!("{}.svg", name)), image).map_err(Error::Io)?;
+            }
+            Some(dir) => {
+                let image = code
+                    .render::<Luma<u8>>()
+                    .min_dimensions(QR_SIZE, QR_SIZE)
+                    .build();
+                image
+                    .save(dir.join(format!("{}.png", name)))
+                    .map_err(Error::Image)?;
+            }
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+/// Name of the QR code of the token at `index`, without characters file systems may not accept.
+fn file_name(index: usize, name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(
+            |c| match c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | '@') {
+                true => c,
+                false => '_',
+            },
+        )
+        .collect();
+    format!("{:02}-{}", index + 1, name)
+}
+
+/// Reads the config, older versions are migrated and tokens given as URI are expanded. The
+/// counters the device wrote next to the config are taken over.
+fn load(path: &Path) -> Result<Config> {
+    let data = fs::read(path).map_err(Error::Io)?;
+    let mut raw: serde_json::Value = serde_json::from_slice(&data).map_err(Error::Json)?;
+    let (mut config, _) = vault::load(&mut raw).map_err(Error::Vault)?;
+    match fs::read(path.with_file_name(vault::COUNTER_FILE)) {
+        Ok(data) => config.apply_counters(&serde_json::from_slice(&data).map_err(Error::Json)?),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => return Err(Error::Io(err)),
+    }
+    Ok(config)
+}
+
+/// Writes the config, through a temporary file so it is never left half written.
+fn save(path: &Path, config: &Config) -> Result<()> {
+    config.validate().map_err(Error::Vault)?;
+    let data = serde_json::to_vec_pretty(config).map_err(Error::Json)?;
+    let temporary = path.with_extension("tmp");
+    fs::write(&temporary, data).map_err(Error::Io)?;
+    fs::rename(&temporary, path).map_err(Error::Io)
+}
+
+/// Asks for the PIN and decrypts the keys, returns the PIN. The keys are encrypted again if they
+/// are `changed`.
+fn unlock(config: &mut Config, changed: bool) -> Result<String> {
+    // a wrong PIN would destroy the keys when they are encrypted again, if it can not be detected
+    // it has to be entered twice
+    let unverified = config.cipher == Cipher::Aes128Ctr || config.tokens.is_empty();
+    let pin = read_pin("PIN: ", changed && unverified)?;
+    config.decrypt(&mut Software, &pin).map_err(Error::Vault)?;
+    Ok(pin)
+}
+
+fn read_pin(prompt: &str, confirm: bool) -> Result<String> {
+    let pin = rpassword::prompt_password(prompt).map_err(Error::Io)?;
+    if pin.len() != vault::PIN_LENGTH || !pin.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(Error::InvalidPin);
+    }
+    if confirm && rpassword::prompt_password("Repeat PIN: ").map_err(Error::Io)? != pin {
+        return Err(Error::PinMismatch);
+    }
+    Ok(pin)
+}
+
+fn kdf(iterations: u32) -> Option<Kdf> {
+    match iterations {
+        0 => None,
+        iterations => Some(Kdf::new(iterations, &mut random)),
+    }
+}
+
+fn random(buffer: &mut [u8]) {
+    getrandom::getrandom(buffer).expect("no random numbers available");
+}
+
+fn find(config: &Config, name: &str) -> Result<usize> {
+    config
+        .tokens
+        .iter()
+        .position(|token| token.name == name)
+        .ok_or_else(|| Error::UnknownToken(name.into()))
+}
+
+/// Decodes a secret as shown by services, which often contains spaces, padding or lowercase letters.
+/// It is normalized like the secrets of URIs in the config, which the device decodes.
+fn decode_secret(secret: &str) -> Result<Vec<u8>> {
+    let secret = otpauth::normalize_secret(secret).ok_or(Error::InvalidSecret)?;
+    Ok(BASE32_NOPAD
+        .decode(secret.as_bytes())
+        .expect("normalized secret is valid base32"))
+}
@@ -0,0 +1,305 @@
+//! Token types of the config, looked up by the `type` of a token.
+use crate::hash::{Algorithm, Hasher, HmacKey};
+use crate::ocra;
+use crate::otp::{self, Token};
+use crate::vault;
+
+/// What a push of the button in app mode does with a token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    /// The code changes over time and is updated every second.
+    Time,
+    /// A push generates the next code.
+    Counter,
+    /// A push asks for a challenge, the code is the response.
+    Challenge,
+}
+
+/// Why no code could be generated for a token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The challenge does not fit the OCRA suite of the token.
+    Ocra(ocra::Error),
+}
+
+impl From<ocra::Error> for Error {
+    fn from(err: ocra::Error) -> Self {
+        Error::Ocra(err)
+    }
+}
+
+/// A token type, looked up by the `type` of the token in the config.
+pub trait OtpAlgorithm: Sync {
+    /// `type` of the token in the config.
+    fn name(&self) -> &'static str;
+
+    fn trigger(&self) -> Trigger {
+        Trigger::Time
+    }
+
+    /// Checks the type specific fields of `token` when the config is loaded.
+    fn validate(&self, _token: &vault::Token) -> Result<(), vault::Error> {
+        Ok(())
+    }
+
+    /// Whether a PIN has to be entered on the device before a code can be generated.
+    fn needs_pin(&self, _token: &vault::Token) -> bool {
+        false
+    }
+
+    /// Whether an entered code can be checked against the token.
+    fn verifiable(&self) -> bool {
+        false
+    }
+
+    /// Whether the counter of the token is used, it is incremented after every code.
+    fn uses_counter(&self, _token: &vault::Token) -> bool {
+        false
+    }
+
+    /// Length of a time step in seconds.
+    fn time_step(&self, token: &vault::Token) -> u64 {
+        token.period
+    }
+
+    /// Number of digits of the challenge.
+    fn challenge_length(&self, _token: &vault::Token) -> u8 {
+        0
+    }
+
+    /// HMAC key of the decoded secret of `token`.
+    fn hmac_key(&self, hasher: &mut dyn Hasher, token: &vault::Token) -> HmacKey {
+        HmacKey::new(hasher, token.algorithm, &token.secret)
+    }
+
+    /// Code of `token`, the `timestamp` is already shifted by the `t0` and offset of the token.
+    /// The `challenge` is only used by [`Trigger::Challenge`] types.
+    fn code(
+        &self,
+        hasher: &mut dyn Hasher,
+        token: &vault::Token,
+        timestamp: u64,
+        challenge: &str,
+    ) -> Result<Token, Error>;
+}
+
+struct Totp;
+
+impl OtpAlgorithm for Totp {
+    fn name(&self) -> &'static str {
+        "totp"
+    }
+
+    fn verifiable(&self) -> bool {
+        true
+    }
+
+    fn code(
+        &self,
+        hasher: &mut dyn Hasher,
+        token: &vault::Token,
+        timestamp: u64,
+        _challenge: &str,
+    ) -> Result<Token, Error> {
+        Ok(otp::totp(
+            hasher,
+            &token.hmac_key,
+            token.digits,
+            token.period,
+            timestamp,
+        ))
+    }
+}
+
+struct Hotp;
+
+impl OtpAlgorithm for Hotp {
+    fn name(&self) -> &'static str {
+        "hotp"
+    }
+
+    fn trigger(&self) -> Trigger {
+        Trigger::Counter
+    }
+
+    fn uses_counter(&self, _token: &vault::Token) -> bool {
+        true
+    }
+
+    fn code(
+        &self,
+        hasher: &mut dyn Hasher,
+        token: &vault::Token,
+        _timestamp: u64,
+        _challenge: &str,
+    ) -> Result<Token, Error> {
+        Ok(otp::hotp(
+            hasher,
+            &token.hmac_key,
+            token.digits,
+            token.counter,
+        ))
+    }
+}
+
+/// Steam Guard, always SHA1 with 30 second periods and 5 symbols.
+struct Steam;
+
+impl OtpAlgorithm for Steam {
+    fn name(&self) -> &'static str {
+        "steam"
+    }
+
+    fn time_step(&self, _token: &vault::Token) -> u64 {
+        otp::STEAM_PERIOD
+    }
+
+    fn hmac_key(&self, hasher: &mut dyn Hasher, token: &vault::Token) -> HmacKey {
+        HmacKey::new(hasher, Algorithm::Sha1, &token.secret)
+    }
+
+    fn code(
+        &self,
+        hasher: &mut dyn Hasher,
+        token: &vault::Token,
+        timestamp: u64,
+        _challenge: &str,
+    ) -> Result<Token, Error> {
+        Ok(otp::steam(hasher, &token.hmac_key, timestamp))
+    }
+}
+
+/// Mobile-OTP, always 10 second periods and 6 hex digits.
+struct Motp;
+
+impl OtpAlgorithm for Motp {
+    fn name(&self) -> &'static str {
+        "motp"
+    }
+
+    fn needs_pin(&self, _token: &vault::Token) -> bool {
+        true
+    }
+
+    fn time_step(&self, _token: &vault::Token) -> u64 {
+        otp::MOTP_PERIOD
+    }
+
+    fn code(
+        &self,
+        _hasher: &mut dyn Hasher,
+        token: &vault::Token,
+        timestamp: u64,
+        _challenge: &str,
+    ) -> Result<Token, Error> {
+        let pin = token.pin.as_deref().unwrap_or_default();
+        Ok(otp::motp(&token.secret, pin, timestamp))
+    }
+}
+
+/// Yandex Key, always 30 second periods and 8 letters.
+struct Yandex;
+
+impl OtpAlgorithm for Yandex {
+    fn name(&self) -> &'static str {
+        "yandex"
+    }
+
+    fn needs_pin(&self, _token: &vault::Token) -> bool {
+        true
+    }
+
+    fn time_step(&self, _token: &vault::Token) -> u64 {
+        otp::YANDEX_PERIOD
+    }
+
+    /// The key depends on the PIN, it is prepared again once the PIN was entered.
+    fn hmac_key(&self, hasher: &mut dyn Hasher, token: &vault::Token) -> HmacKey {
+        let pin = token.pin.as_deref().unwrap_or_default();
+        otp::yandex_key(hasher, &token.secret, pin)
+    }
+
+    fn code(
+        &self,
+        hasher: &mut dyn Hasher,
+        token: &vault::Token,
+        timestamp: u64,
+        _challenge: &str,
+    ) -> Result<Token, Error> {
+        Ok(otp::yandex(hasher, &token.hmac_key, timestamp))
+    }
+}
+
+/// OCRA challenge-response, all parameters are taken from the suite.
+struct Ocra;
+
+impl OtpAlgorithm for Ocra {
+    fn name(&self) -> &'static str {
+        "ocra"
+    }
+
+    fn trigger(&self) -> Trigger {
+        Trigger::Challenge
+    }
+
+    /// Only numeric challenges can be entered with the rotary encoder.
+    fn validate(&self, token: &vault::Token) -> Result<(), vault::Error> {
+        match token.suite.as_deref().map(ocra::Suite::parse) {
+            Some(Ok(suite))
+                if suite.challenge == ocra::Challenge::Numeric && suite.session.is_none() =>
+            {
+                Ok(())
+            }
+            _ => Err(vault::Error::InvalidSuite(token.name.clone())),
+        }
+    }
+
+    fn needs_pin(&self, token: &vault::Token) -> bool {
+        token.ocra_suite().is_some_and(|suite| suite.pin.is_some())
+    }
+
+    fn uses_counter(&self, token: &vault::Token) -> bool {
+        token.ocra_suite().is_some_and(|suite| suite.counter)
+    }
+
+    fn challenge_length(&self, token: &vault::Token) -> u8 {
+        token.ocra_suite().map_or(0, |suite| suite.challenge_length)
+    }
+
+    fn hmac_key(&self, hasher: &mut dyn Hasher, token: &vault::Token) -> HmacKey {
+        let algorithm = token.ocra_suite().map(|suite| suite.algorithm);
+        HmacKey::new(hasher, algorithm.unwrap_or_default(), &token.secret)
+    }
+
+    fn code(
+        &self,
+        hasher: &mut dyn Hasher,
+        token: &vault::Token,
+        timestamp: u64,
+        challenge: &str,
+    ) -> Result<Token, Error> {
+        let suite = token.ocra_suite().ok_or(ocra::Error::InvalidSuite)?;
+        let input = ocra::Input {
+            counter: token.counter,
+            challenge,
+            pin: token.pin.as_deref().unwrap_or_default(),
+            timestamp,
+            ..Default::default()
+        };
+        Ok(ocra::ocra(hasher, suite, &token.hmac_key, &input)?)
+    }
+}
+
+/// Type of tokens without a `type`.
+pub const DEFAULT: &dyn OtpAlgorithm = &Totp;
+
+/// All token types, new types only have to be added here.
+static ALGORITHMS: &[&dyn OtpAlgorithm] = &[&Totp, &Hotp, &Steam, &Motp, &Yandex, &Ocra];
+
+/// The token type with the `type` `name`.
+pub fn find(name: &str) -> Option<&'static dyn OtpAlgorithm> {
+    ALGORITHMS
+        .iter()
+        .copied()
+        .find(|algorithm| algorithm.name() == name)
+}
@@ -0,0 +1,170 @@
+use core::fmt;
+use core::ops::Deref;
+
+use serde::{Deserialize, Serialize};
+
+pub const MAX_DIGEST_SIZE: usize = 64;
+pub const MAX_BLOCK_SIZE: usize = 128;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Algorithm {
+    #[default]
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl Algorithm {
+    /// Parses the [`name`](Self::name), ignoring the case.
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Algorithm::Sha1, Algorithm::Sha256, Algorithm::Sha512]
+            .into_iter()
+            .find(|algorithm| algorithm.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Name as used in the config and in otpauth URIs.
+    pub fn name(self) -> &'static str {
+        match self {
+            Algorithm::Sha1 => "SHA1",
+            Algorithm::Sha256 => "SHA256",
+            Algorithm::Sha512 => "SHA512",
+        }
+    }
+
+    pub fn digest_size(self) -> usize {
+        match self {
+            Algorithm::Sha1 => 20,
+            Algorithm::Sha256 => 32,
+            Algorithm::Sha512 => 64,
+        }
+    }
+
+    pub fn block_size(self) -> usize {
+        match self {
+            Algorithm::Sha1 | Algorithm::Sha256 => 64,
+            Algorithm::Sha512 => 128,
+        }
+    }
+}
+
+/// Output of a hash function, large enough for all supported algorithms.
+#[derive(Clone, Copy)]
+pub struct Digest {
+    bytes: [u8; MAX_DIGEST_SIZE],
+    len: usize,
+}
+
+impl Digest {
+    pub fn new(algorithm: Algorithm) -> Self {
+        Digest {
+            bytes: [0u8; MAX_DIGEST_SIZE],
+            len: algorithm.digest_size(),
+        }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.bytes[..self.len]
+    }
+}
+
+impl Deref for Digest {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+}
+
+/// Implementation of the supported hash functions, e.g. the SHA peripheral of the ESP32.
+pub trait Hasher {
+    /// Hashes the concatenation of `parts`.
+    fn digest(&mut self, algorithm: Algorithm, parts: &[&[u8]]) -> Digest;
+}
+
+const IPAD: u8 = 0x36;
+const OPAD: u8 = 0x5C;
+
+/// Key of an HMAC (RFC 2104) with the inner and outer padded keys computed up front, so signing
+/// a message takes two hash operations and no further key handling.
+#[derive(Clone)]
+pub struct HmacKey {
+    algorithm: Algorithm,
+    inner: [u8; MAX_BLOCK_SIZE],
+    outer: [u8; MAX_BLOCK_SIZE],
+}
+
+impl HmacKey {
+    pub fn new<H: Hasher + ?Sized>(hasher: &mut H, algorithm: Algorithm, key: &[u8]) -> Self {
+        let mut inner = [0u8; MAX_BLOCK_SIZE];
+        if key.len() > algorithm.block_size() {
+            let key_hash = hasher.digest(algorithm, &[key]);
+            inner[..key_hash.len()].copy_from_slice(&key_hash);
+        } else {
+            inner[..key.len()].copy_from_slice(key);
+        }
+        let mut outer = inner;
+        for b in inner.iter_mut() {
+            *b ^= IPAD;
+        }
+        for b in outer.iter_mut() {
+            *b ^= OPAD;
+        }
+        HmacKey {
+            algorithm,
+            inner,
+            outer,
+        }
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    /// HMAC of `message`.
+    pub fn sign<H: Hasher + ?Sized>(&self, hasher: &mut H, message: &[u8]) -> Digest {
+        self.sign_concat(hasher, message, &[])
+    }
+
+    /// HMAC of the concatenation of `first` and `second`, without copying them together.
+    pub(crate) fn sign_concat<H: Hasher + ?Sized>(
+        &self,
+        hasher: &mut H,
+        first: &[u8],
+        second: &[u8],
+    ) -> Digest {
+        let block_size = self.algorithm.block_size();
+        let inner = hasher.digest(self.algorithm, &[&self.inner[..block_size], first, second]);
+        hasher.digest(self.algorithm, &[&self.outer[..block_size], &inner])
+    }
+}
+
+impl fmt::Debug for HmacKey {
+    /// Leaves out the key material.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HmacKey")
+            .field("algorithm", &self.algorithm)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for HmacKey {
+    /// SHA1 with an empty key.
+    fn default() -> Self {
+        HmacKey {
+            algorithm: Algorithm::Sha1,
+            inner: [IPAD; MAX_BLOCK_SIZE],
+            outer: [OPAD; MAX_BLOCK_SIZE],
+        }
+    }
+}
+
+/// HMAC (RFC 2104) on top of any [`Hasher`].
+pub fn hmac<H: Hasher + ?Sized>(
+    hasher: &mut H,
+    algorithm: Algorithm,
+    key: &[u8],
+    message: &[u8],
+) -> Digest {
+    HmacKey::new(hasher, algorithm, key).sign(hasher, message)
+}
@@ -0,0 +1,209 @@
+//! JSON exports of Aegis Authenticator, see
+//! <https://github.com/beemdevelopment/Aegis/blob/master/docs/vault.md>.
+//!
+//! In an encrypted export the `db` is encrypted with AES-256-GCM and a random master key, which
+//! in turn is encrypted by every key slot. Password slots derive their key with scrypt.
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use aes_gcm::aead::{AeadInPlace, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce, Tag};
+use data_encoding::{BASE64, HEXLOWER_PERMISSIVE};
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{Fields, Report};
+
+const KEY_SIZE: usize = 32;
+const NONCE_SIZE: usize = 12;
+const TAG_SIZE: usize = 16;
+/// Type of the key slots derived from a password.
+const PASSWORD_SLOT: u8 = 1;
+
+#[derive(Debug)]
+pub enum Error {
+    Deserialize(serde_json::Error),
+    /// A hex or base64 field of the export is invalid.
+    InvalidEncoding,
+    /// The export is encrypted but no password was given.
+    PasswordRequired,
+    /// The export is encrypted and has no password slot, e.g. only biometric ones.
+    NoPasswordSlot,
+    /// The scrypt parameters of the slot are invalid.
+    InvalidParams,
+    WrongPassword,
+    /// The master key does not decrypt the `db`.
+    InvalidDatabase,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Backup {
+    header: Header,
+    /// The [`Database`], base64 encoded and encrypted if the header has slots.
+    db: Value,
+}
+
+#[derive(Deserialize, Debug)]
+struct Header {
+    slots: Option<Vec<Slot>>,
+    params: Option<KeyParams>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Slot {
+    #[serde(rename = "type")]
+    kind: u8,
+    /// The encrypted master key.
+    key: String,
+    key_params: KeyParams,
+    n: Option<u64>,
+    r: Option<u32>,
+    p: Option<u32>,
+    salt: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct KeyParams {
+    nonce: String,
+    tag: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Database {
+    entries: Vec<Entry>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Entry {
+    #[serde(rename = "type")]
+    kind: String,
+    name: String,
+    #[serde(default)]
+    issuer: String,
+    info: Info,
+}
+
+#[derive(Deserialize, Debug)]
+struct Info {
+    secret: String,
+    algo: Option<String>,
+    digits: Option<u8>,
+    period: Option<u64>,
+    counter: Option<u64>,
+    /// PIN of mOTP and Yandex entries, it is entered on the device instead.
+    pin: Option<String>,
+}
+
+impl Backup {
+    pub fn parse(data: &[u8]) -> Result<Self, Error> {
+        serde_json::from_slice(data).map_err(Error::Deserialize)
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.db.is_string()
+    }
+
+    /// Converts the entries, the `password` is only needed if the export
+    /// [is encrypted](Self::is_encrypted).
+    pub fn import(&self, password: Option<&str>) -> Result<Report, Error> {
+        let database: Database = match &self.db {
+            Value::String(db) => {
+                let password = password.ok_or(Error::PasswordRequired)?;
+                let data = self.decrypt(db, password)?;
+                serde_json::from_slice(&data).map_err(Error::Deserialize)?
+            }
+            db => Database::deserialize(db).map_err(Error::Deserialize)?,
+        };
+        let mut report = Report::default();
+        for entry in database.entries.iter() {
+            let info = &entry.info;
+            let fields = Fields {
+                name: &entry.name,
+                issuer: &entry.issuer,
+                kind: &entry.kind,
+                algorithm: info.algo.as_deref(),
+                secret: &info.secret,
+                digits: info.digits,
+                period: info.period,
+                counter: info.counter,
+                pin: info.pin.as_deref(),
+            };
+            report.add(fields.label(), fields.token());
+        }
+        Ok(report)
+    }
+
+    fn decrypt(&self, db: &str, password: &str) -> Result<Vec<u8>, Error> {
+        let slots = self.header.slots.as_deref().unwrap_or_default();
+        let params = self.header.params.as_ref().ok_or(Error::InvalidEncoding)?;
+        let mut master_key = None;
+        for slot in slots.iter().filter(|slot| slot.kind == PASSWORD_SLOT) {
+            if let Some(key) = slot.decrypt(password)? {
+                master_key = Some(key);
+                break;
+            }
+        }
+        let master_key = match master_key {
+            Some(key) => key,
+            None if slots.iter().any(|slot| slot.kind == PASSWORD_SLOT) => {
+                return Err(Error::WrongPassword)
+            }
+            None => return Err(Error::NoPasswordSlot),
+        };
+
+        let data = BASE64
+            .decode(db.as_bytes())
+            .map_err(|_| Error::InvalidEncoding)?;
+        decrypt(&master_key, params, data).ok_or(Error::InvalidDatabase)
+    }
+}
+
+impl Slot {
+    /// The master key, `None` if the password does not match.
+    fn decrypt(&self, password: &str) -> Result<Option<[u8; KEY_SIZE]>, Error> {
+        let (Some(n), Some(r), Some(p), Some(salt)) = (self.n, self.r, self.p, &self.salt) else {
+            return Err(Error::InvalidParams);
+        };
+        if !n.is_power_of_two() {
+            return Err(Error::InvalidParams);
+        }
+        let params = scrypt::Params::new(n.trailing_zeros() as u8, r, p, KEY_SIZE)
+            .map_err(|_| Error::InvalidParams)?;
+        let mut key = [0u8; KEY_SIZE];
+        scrypt::scrypt(password.as_bytes(), &hex(salt)?, &params, &mut key)
+            .map_err(|_| Error::InvalidParams)?;
+
+        let master_key = match decrypt(&key, &self.key_params, hex(&self.key)?) {
+            Some(master_key) => master_key,
+            None => return Ok(None),
+        };
+        master_key
+            .try_into()
+            .map(Some)
+            .map_err(|_| Error::InvalidEncoding)
+    }
+}
+
+fn hex(value: &str) -> Result<Vec<u8>, Error> {
+    HEXLOWER_PERMISSIVE
+        .decode(value.as_bytes())
+        .map_err(|_| Error::InvalidEncoding)
+}
+
+/// AES-256-GCM with the detached nonce and tag of `params`, `None` if the tag does not match.
+fn decrypt(key: &[u8; KEY_SIZE], params: &KeyParams, mut data: Vec<u8>) -> Option<Vec<u8>> {
+    let nonce = hex(&params.nonce).ok()?;
+    let tag = hex(&params.tag).ok()?;
+    if nonce.len() != NONCE_SIZE || tag.len() != TAG_SIZE {
+        return None;
+    }
+    Aes256Gcm::new(key.into())
+        .decrypt_in_place_detached(
+            Nonce::from_slice(&nonce),
+            b"",
+            &mut data,
+            Tag::from_slice(&tag),
+        )
+        .ok()?;
+    Some(data)
+}
@@ -0,0 +1,58 @@
+//! Plaintext JSON backups of andOTP, an array of entries.
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use data_encoding::{BASE32_NOPAD, HEXLOWER_PERMISSIVE};
+use serde::Deserialize;
+
+use super::{Error, Fields, Reason, Report};
+
+#[derive(Deserialize, Debug)]
+struct Entry {
+    /// Base32, the init secret as hex for mOTP.
+    secret: String,
+    #[serde(default)]
+    issuer: String,
+    #[serde(default)]
+    label: String,
+    #[serde(rename = "type")]
+    kind: String,
+    algorithm: Option<String>,
+    digits: Option<u8>,
+    period: Option<u64>,
+    counter: Option<u64>,
+}
+
+/// Converts the entries, encrypted backups (`.json.aes`) are not JSON and are rejected.
+pub fn import(data: &[u8]) -> Result<Report, Error> {
+    let entries: Vec<Entry> = serde_json::from_slice(data).map_err(Error::Deserialize)?;
+    let mut report = Report::default();
+    for entry in entries.iter() {
+        let mut fields = Fields {
+            name: &entry.label,
+            issuer: &entry.issuer,
+            kind: &entry.kind,
+            algorithm: entry.algorithm.as_deref(),
+            secret: &entry.secret,
+            digits: entry.digits,
+            period: entry.period,
+            counter: entry.counter,
+            pin: None,
+        };
+        let secret;
+        let token = if entry.kind.eq_ignore_ascii_case("motp") {
+            match HEXLOWER_PERMISSIVE.decode(entry.secret.as_bytes()) {
+                Ok(decoded) => {
+                    secret = BASE32_NOPAD.encode(&decoded);
+                    fields.secret = &secret;
+                    fields.token()
+                }
+                Err(_) => Err(Reason::InvalidSecret),
+            }
+        } else {
+            fields.token()
+        };
+        report.add(fields.label(), token);
+    }
+    Ok(report)
+}
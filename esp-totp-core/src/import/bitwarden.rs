@@ -0,0 +1,72 @@
+//! Unencrypted JSON exports of Bitwarden, the items with a `login.totp`.
+//!
+//! The `totp` is an otpauth URI, a `steam://` secret or a bare base32 TOTP secret.
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use serde::Deserialize;
+
+use super::{Error, Fields, Reason, Report};
+use crate::otpauth;
+
+#[derive(Deserialize, Debug)]
+struct Export {
+    #[serde(default)]
+    encrypted: bool,
+    #[serde(default)]
+    items: Vec<Item>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Item {
+    name: String,
+    login: Option<Login>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Login {
+    username: Option<String>,
+    totp: Option<String>,
+}
+
+/// Converts the items with a TOTP secret, the others are ignored.
+pub fn import(data: &[u8]) -> Result<Report, Error> {
+    let export: Export = serde_json::from_slice(data).map_err(Error::Deserialize)?;
+    if export.encrypted {
+        return Err(Error::Encrypted);
+    }
+    let mut report = Report::default();
+    for item in export.items.iter() {
+        let Some(login) = &item.login else {
+            continue;
+        };
+        let totp = match login.totp.as_deref().map(str::trim) {
+            None | Some("") => continue,
+            Some(totp) => totp,
+        };
+        let mut fields = Fields {
+            name: login.username.as_deref().unwrap_or_default(),
+            issuer: &item.name,
+            kind: "totp",
+            secret: totp,
+            ..Fields::default()
+        };
+
+        let token = if let Some(secret) = totp.strip_prefix("steam://") {
+            fields.kind = "steam";
+            fields.secret = secret;
+            fields.token()
+        } else if totp.contains("://") {
+            otpauth::parse(totp)
+                .map(|mut token| {
+                    token.issuer = token.issuer.or_else(|| Some(item.name.clone()));
+                    token
+                })
+                .map_err(Reason::InvalidUri)
+        } else {
+            fields.token()
+        };
+        report.add(fields.label(), token);
+    }
+    Ok(report)
+}
@@ -0,0 +1,132 @@
+//! Backups of other authenticator apps, converted to [`Token`]s with their secret as base32
+//! `key`.
+//!
+//! Entries which can not be converted are skipped and listed in the [`Report`]. Google
+//! Authenticator exports are read by [`migration`](crate::migration), as the firmware accepts
+//! them in the config.
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::algorithm;
+use crate::hash::Algorithm;
+use crate::otpauth::{self, normalize_secret};
+use crate::vault::{self, Token};
+
+pub mod aegis;
+pub mod andotp;
+pub mod bitwarden;
+pub mod twofas;
+
+#[derive(Debug)]
+pub enum Error {
+    Deserialize(serde_json::Error),
+    /// The export is encrypted, only plaintext exports of this app are supported.
+    Encrypted,
+}
+
+/// Why an entry was skipped.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Reason {
+    UnsupportedType(String),
+    UnsupportedAlgorithm(String),
+    /// The secret is not valid base32 (or hex for mOTP).
+    InvalidSecret,
+    InvalidUri(otpauth::Error),
+    /// The field with this name is not valid in the config, e.g. `digits` of 9.
+    InvalidParameter(&'static str),
+}
+
+/// The converted tokens and the skipped entries with their name.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub tokens: Vec<Token>,
+    pub skipped: Vec<(String, Reason)>,
+}
+
+impl Report {
+    /// Adds the `token` if it would be valid in the config, so a single entry can not fail the
+    /// whole import.
+    fn add(&mut self, label: String, token: Result<Token, Reason>) {
+        let token = token.and_then(|token| match token.validate() {
+            Ok(()) => Ok(token),
+            Err(err) => Err(Reason::InvalidParameter(match err {
+                vault::Error::InvalidPeriod(_) => "period",
+                vault::Error::InvalidDigits(_) => "digits",
+                vault::Error::InvalidPinLength(_) => "pin_length",
+                vault::Error::InvalidSuite(_) => "suite",
+                _ => "type",
+            })),
+        });
+        match token {
+            Ok(token) => self.tokens.push(token),
+            Err(reason) => self.skipped.push((label, reason)),
+        }
+    }
+}
+
+/// Types with a fixed length of the code and time step, the values of the export are ignored.
+const FIXED_CODE: [&str; 3] = ["steam", "motp", "yandex"];
+
+/// The fields of an entry most apps store, converted by [`Fields::token`].
+#[derive(Default)]
+struct Fields<'a> {
+    name: &'a str,
+    issuer: &'a str,
+    /// Name of the type, in any case.
+    kind: &'a str,
+    algorithm: Option<&'a str>,
+    /// Base32 encoded secret.
+    secret: &'a str,
+    digits: Option<u8>,
+    period: Option<u64>,
+    counter: Option<u64>,
+    pin: Option<&'a str>,
+}
+
+impl Fields<'_> {
+    /// `ISSUER: NAME` to identify a skipped entry.
+    fn label(&self) -> String {
+        match (self.issuer, self.name) {
+            ("", name) => name.into(),
+            (issuer, "") => issuer.into(),
+            (issuer, name) => format!("{}: {}", issuer, name),
+        }
+    }
+
+    fn token(&self) -> Result<Token, Reason> {
+        let kind = self.kind.to_ascii_lowercase();
+        if algorithm::find(&kind).is_none() {
+            return Err(Reason::UnsupportedType(kind));
+        }
+        let algorithm = match self.algorithm {
+            // mOTP always uses MD5, which some apps store as its algorithm
+            _ if kind == "motp" => Algorithm::default(),
+            None | Some("") => Algorithm::default(),
+            Some(name) => Algorithm::from_name(name)
+                .ok_or_else(|| Reason::UnsupportedAlgorithm(name.into()))?,
+        };
+        let key = normalize_secret(self.secret).ok_or(Reason::InvalidSecret)?;
+
+        let mut token = Token::new(self.name.trim().into(), Vec::new());
+        token.issuer = Some(self.issuer.trim())
+            .filter(|issuer| !issuer.is_empty())
+            .map(Into::into);
+        if token.name.is_empty() {
+            token.name = token.issuer.clone().unwrap_or_default();
+        }
+        token.key = key;
+        token.kind = kind;
+        token.algorithm = algorithm;
+        // e.g. Steam codes have 5 characters, which are not valid digits of the config
+        if !FIXED_CODE.contains(&token.kind.as_str()) {
+            token.digits = self.digits.unwrap_or(token.digits);
+            token.period = self.period.unwrap_or(token.period);
+        }
+        token.counter = self.counter.unwrap_or(token.counter);
+        if let Some(pin) = self.pin.filter(|pin| !pin.is_empty()) {
+            token.pin_length = pin.chars().count().try_into().unwrap_or(u8::MAX);
+        }
+        Ok(token)
+    }
+}
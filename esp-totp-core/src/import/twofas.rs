@@ -0,0 +1,65 @@
+//! Unencrypted `.2fas` backups of 2FAS Auth.
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use serde::Deserialize;
+
+use super::{Error, Fields, Report};
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Backup {
+    #[serde(default)]
+    services: Vec<Service>,
+    /// Set instead of the `services` if the backup is protected by a password.
+    services_encrypted: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Service {
+    name: String,
+    secret: String,
+    otp: Otp,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Otp {
+    account: Option<String>,
+    label: Option<String>,
+    issuer: Option<String>,
+    digits: Option<u8>,
+    period: Option<u64>,
+    algorithm: Option<String>,
+    counter: Option<u64>,
+    token_type: Option<String>,
+}
+
+pub fn import(data: &[u8]) -> Result<Report, Error> {
+    let backup: Backup = serde_json::from_slice(data).map_err(Error::Deserialize)?;
+    if backup.services_encrypted.is_some() {
+        return Err(Error::Encrypted);
+    }
+    let mut report = Report::default();
+    for service in backup.services.iter() {
+        let otp = &service.otp;
+        let issuer = otp.issuer.as_deref().filter(|issuer| !issuer.is_empty());
+        let fields = Fields {
+            name: otp
+                .account
+                .as_deref()
+                .or(otp.label.as_deref())
+                .unwrap_or_default(),
+            issuer: issuer.unwrap_or(&service.name),
+            kind: otp.token_type.as_deref().unwrap_or("totp"),
+            algorithm: otp.algorithm.as_deref(),
+            secret: &service.secret,
+            digits: otp.digits,
+            period: otp.period,
+            counter: otp.counter,
+            pin: None,
+        };
+        report.add(fields.label(), fields.token());
+    }
+    Ok(report)
+}
@@ -0,0 +1,29 @@
+//! Key derivation from the PIN.
+use crate::hash::{Algorithm, Hasher, HmacKey};
+
+/// PBKDF2 (RFC 8018) with an HMAC of `algorithm` as pseudorandom function, fills `output` with
+/// the derived key.
+///
+/// The password is only prepared once, so every iteration takes two hash operations.
+pub fn pbkdf2<H: Hasher + ?Sized>(
+    hasher: &mut H,
+    algorithm: Algorithm,
+    password: &[u8],
+    salt: &[u8],
+    iterations: u32,
+    output: &mut [u8],
+) {
+    let key = HmacKey::new(hasher, algorithm, password);
+    for (i, block) in output.chunks_mut(algorithm.digest_size()).enumerate() {
+        let index = (i as u32 + 1).to_be_bytes();
+        let mut u = key.sign_concat(hasher, salt, &index);
+        let mut t = u;
+        for _ in 1..iterations {
+            u = key.sign(hasher, &u);
+            for (t, u) in t.as_mut_slice().iter_mut().zip(u.iter()) {
+                *t ^= u;
+            }
+        }
+        block.copy_from_slice(&t[..block.len()]);
+    }
+}
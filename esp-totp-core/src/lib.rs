@@ -0,0 +1,24 @@
+//! Hardware independent parts of the ESP32 TOTP gadget, shared between the firmware and the host.
+#![no_std]
+
+#[cfg(feature = "vault")]
+extern crate alloc;
+
+#[cfg(feature = "vault")]
+pub mod algorithm;
+pub mod hash;
+#[cfg(feature = "import")]
+pub mod import;
+pub mod kdf;
+#[cfg(feature = "vault")]
+pub mod migration;
+pub mod ocra;
+pub mod otp;
+#[cfg(feature = "vault")]
+pub mod otpauth;
+#[cfg(feature = "software")]
+pub mod software;
+#[cfg(feature = "vault")]
+pub mod vault;
+
+pub use hash::{Algorithm, Hasher, HmacKey};
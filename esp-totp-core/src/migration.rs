@@ -0,0 +1,326 @@
+//! `otpauth-migration://offline?data=...` URIs of the Google Authenticator export, a base64
+//! encoded protobuf `MigrationPayload` with the tokens of one QR code.
+//!
+//! Large exports are split into several QR codes, the parts of a batch share its `id`, see
+//! [`check_batches`].
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use data_encoding::{BASE32_NOPAD, BASE64};
+
+use crate::hash::Algorithm;
+use crate::otpauth;
+use crate::vault::Token;
+
+const PREFIX: &str = "otpauth-migration://offline?";
+
+/// Tokens in one part of an [`export`], so the QR code stays readable.
+pub const TOKENS_PER_PART: usize = 10;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The URI does not start with `otpauth-migration://offline?`.
+    InvalidScheme,
+    MissingData,
+    /// Invalid percent-encoding or base64.
+    InvalidEncoding,
+    /// The data is not a valid `MigrationPayload`.
+    InvalidPayload,
+    MissingSecret,
+    UnsupportedAlgorithm,
+    UnsupportedDigits,
+    UnsupportedType,
+    /// Google Authenticator only supports TOTP tokens with a period of 30 seconds.
+    UnsupportedPeriod,
+    /// The token has a `t0` or `offset_seconds`, the app would show other codes.
+    UnsupportedTimeShift,
+    /// Not all parts of the batch with this id were given, or some of them twice.
+    IncompleteBatch(i32),
+}
+
+/// Position of a [`Payload`] in a multi-part export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Batch {
+    pub id: i32,
+    pub index: u32,
+    pub size: u32,
+}
+
+#[derive(Debug)]
+pub struct Payload {
+    /// The tokens with their secret as base32 `key`.
+    pub tokens: Vec<Token>,
+    pub batch: Batch,
+}
+
+/// Parses one migration URI.
+pub fn parse(uri: &str) -> Result<Payload, Error> {
+    let query = match uri.get(..PREFIX.len()) {
+        Some(prefix) if prefix.eq_ignore_ascii_case(PREFIX) => &uri[PREFIX.len()..],
+        _ => return Err(Error::InvalidScheme),
+    };
+    let data = query
+        .split('&')
+        .find_map(|parameter| parameter.strip_prefix("data="))
+        .ok_or(Error::MissingData)?;
+    let data = otpauth::decode(data).map_err(|_| Error::InvalidEncoding)?;
+    // a `+` is often left unescaped
+    let data = data.replace(' ', "+");
+    let data = BASE64
+        .decode(data.as_bytes())
+        .map_err(|_| Error::InvalidEncoding)?;
+    decode_payload(&data)
+}
+
+/// Checks that every batch is complete, i.e. each part from 0 to its size was given once.
+pub fn check_batches(batches: &[Batch]) -> Result<(), Error> {
+    for batch in batches.iter() {
+        let parts: Vec<&Batch> = batches
+            .iter()
+            .filter(|other| other.id == batch.id)
+            .collect();
+        let complete = parts.len() == batch.size as usize
+            && parts.iter().all(|part| part.size == batch.size)
+            && (0..batch.size).all(|index| parts.iter().any(|part| part.index == index));
+        if !complete {
+            return Err(Error::IncompleteBatch(batch.id));
+        }
+    }
+    Ok(())
+}
+
+/// Parses the URIs of all QR codes of an export, the tokens are returned in the order of the
+/// parts.
+pub fn parse_batch<'a>(uris: impl IntoIterator<Item = &'a str>) -> Result<Vec<Token>, Error> {
+    let mut payloads = uris
+        .into_iter()
+        .map(parse)
+        .collect::<Result<Vec<Payload>, Error>>()?;
+    let batches: Vec<Batch> = payloads.iter().map(|payload| payload.batch).collect();
+    check_batches(&batches)?;
+    payloads.sort_by_key(|payload| (payload.batch.id, payload.batch.index));
+    Ok(payloads
+        .into_iter()
+        .flat_map(|payload| payload.tokens)
+        .collect())
+}
+
+/// Whether Google Authenticator can represent the token, HOTP or TOTP tokens with a period of 30
+/// seconds and 6 or 8 digits, without a `t0` or `offset_seconds`.
+pub fn check(token: &Token) -> Result<(), Error> {
+    match token.kind.as_str() {
+        "totp" if token.period != 30 => return Err(Error::UnsupportedPeriod),
+        "totp" if token.is_time_shifted() => return Err(Error::UnsupportedTimeShift),
+        "totp" | "hotp" => {}
+        _ => return Err(Error::UnsupportedType),
+    }
+    if !matches!(token.digits, 6 | 8) {
+        return Err(Error::UnsupportedDigits);
+    }
+    Ok(())
+}
+
+/// The URIs of the parts of an export of the decrypted tokens, which have to pass [`check`].
+/// The `batch_id` should be random, it identifies the export.
+pub fn export(tokens: &[&Token], batch_id: i32) -> Vec<String> {
+    let parts: Vec<&[&Token]> = tokens.chunks(TOKENS_PER_PART).collect();
+    let mut uris = Vec::with_capacity(parts.len());
+    for (index, tokens) in parts.iter().enumerate() {
+        let mut data = Vec::new();
+        for token in tokens.iter() {
+            write_bytes(&mut data, 1, &encode_parameters(token));
+        }
+        write_varint(&mut data, 2, 1);
+        write_varint(&mut data, 3, parts.len() as u64);
+        write_varint(&mut data, 4, index as u64);
+        // negative int32 values are sign extended
+        write_varint(&mut data, 5, i64::from(batch_id) as u64);
+        let data = otpauth::encode(&BASE64.encode(&data));
+        uris.push(format!("{}data={}", PREFIX, data));
+    }
+    uris
+}
+
+fn encode_parameters(token: &Token) -> Vec<u8> {
+    let algorithm = match token.algorithm {
+        Algorithm::Sha1 => 1,
+        Algorithm::Sha256 => 2,
+        Algorithm::Sha512 => 3,
+    };
+    let (kind, counter) = match token.kind.as_str() {
+        "hotp" => (1, token.counter),
+        _ => (2, 0),
+    };
+    let mut data = Vec::new();
+    write_bytes(&mut data, 1, &token.secret);
+    write_bytes(&mut data, 2, token.name.as_bytes());
+    write_bytes(
+        &mut data,
+        3,
+        token.issuer.as_deref().unwrap_or_default().as_bytes(),
+    );
+    write_varint(&mut data, 4, algorithm);
+    write_varint(&mut data, 5, if token.digits == 8 { 2 } else { 1 });
+    write_varint(&mut data, 6, kind);
+    write_varint(&mut data, 7, counter);
+    data
+}
+
+fn decode_payload(data: &[u8]) -> Result<Payload, Error> {
+    let mut tokens = Vec::new();
+    // exports without batch fields consist of a single part
+    let mut batch = Batch {
+        id: 0,
+        index: 0,
+        size: 1,
+    };
+    let mut reader = Reader(data);
+    while let Some((field, value)) = reader.field()? {
+        match (field, value) {
+            (1, Value::Bytes(parameters)) => tokens.push(decode_parameters(parameters)?),
+            (3, Value::Varint(size)) => batch.size = size.max(1) as u32,
+            (4, Value::Varint(index)) => batch.index = index as u32,
+            (5, Value::Varint(id)) => batch.id = id as i32,
+            _ => {}
+        }
+    }
+    Ok(Payload { tokens, batch })
+}
+
+/// Decodes `OtpParameters`, unset enums are the defaults of Google Authenticator.
+fn decode_parameters(data: &[u8]) -> Result<Token, Error> {
+    let mut secret: &[u8] = &[];
+    let mut name = String::new();
+    let mut issuer = String::new();
+    let mut token = Token::new(String::new(), Vec::new());
+    let mut reader = Reader(data);
+    while let Some((field, value)) = reader.field()? {
+        match (field, value) {
+            (1, Value::Bytes(bytes)) => secret = bytes,
+            (2, Value::Bytes(bytes)) => name = string(bytes)?,
+            (3, Value::Bytes(bytes)) => issuer = string(bytes)?,
+            (4, Value::Varint(algorithm)) => {
+                token.algorithm = match algorithm {
+                    0 | 1 => Algorithm::Sha1,
+                    2 => Algorithm::Sha256,
+                    3 => Algorithm::Sha512,
+                    _ => return Err(Error::UnsupportedAlgorithm),
+                }
+            }
+            (5, Value::Varint(digits)) => {
+                token.digits = match digits {
+                    0 | 1 => 6,
+                    2 => 8,
+                    _ => return Err(Error::UnsupportedDigits),
+                }
+            }
+            (6, Value::Varint(kind)) => {
+                token.kind = match kind {
+                    1 => "hotp".into(),
+                    0 | 2 => "totp".into(),
+                    _ => return Err(Error::UnsupportedType),
+                }
+            }
+            (7, Value::Varint(counter)) => token.counter = counter,
+            _ => {}
+        }
+    }
+    if secret.is_empty() {
+        return Err(Error::MissingSecret);
+    }
+    token.key = BASE32_NOPAD.encode(secret);
+
+    let (label_issuer, account) = otpauth::split_label(&name);
+    token.issuer = Some(issuer)
+        .filter(|issuer| !issuer.is_empty())
+        .or(label_issuer.map(Into::into));
+    token.name = match account {
+        "" => token.issuer.clone().unwrap_or_default(),
+        account => account.into(),
+    };
+    Ok(token)
+}
+
+fn string(bytes: &[u8]) -> Result<String, Error> {
+    String::from_utf8(bytes.to_vec()).map_err(|_| Error::InvalidPayload)
+}
+
+fn varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn write_varint(out: &mut Vec<u8>, field: u64, value: u64) {
+    varint(out, field << 3);
+    varint(out, value);
+}
+
+fn write_bytes(out: &mut Vec<u8>, field: u64, value: &[u8]) {
+    varint(out, field << 3 | 2);
+    varint(out, value.len() as u64);
+    out.extend_from_slice(value);
+}
+
+enum Value<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+/// Reads the fields of a protobuf message.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    /// Next field number and value, `None` at the end of the message.
+    fn field(&mut self) -> Result<Option<(u64, Value<'a>)>, Error> {
+        if self.0.is_empty() {
+            return Ok(None);
+        }
+        let key = self.varint()?;
+        let value = match key & 7 {
+            0 => Value::Varint(self.varint()?),
+            1 => {
+                self.take(8)?;
+                Value::Fixed
+            }
+            2 => {
+                let length = self.varint()?;
+                Value::Bytes(
+                    self.take(usize::try_from(length).map_err(|_| Error::InvalidPayload)?)?,
+                )
+            }
+            5 => {
+                self.take(4)?;
+                Value::Fixed
+            }
+            _ => return Err(Error::InvalidPayload),
+        };
+        Ok(Some((key >> 3, value)))
+    }
+
+    fn varint(&mut self) -> Result<u64, Error> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let (&byte, rest) = self.0.split_first().ok_or(Error::InvalidPayload)?;
+            self.0 = rest;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(Error::InvalidPayload)
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], Error> {
+        if length > self.0.len() {
+            return Err(Error::InvalidPayload);
+        }
+        let (bytes, rest) = self.0.split_at(length);
+        self.0 = rest;
+        Ok(bytes)
+    }
+}
@@ -0,0 +1,278 @@
+//! OCRA challenge-response algorithm (RFC 6287).
+use core::fmt;
+use core::str::FromStr;
+
+use crate::hash::{Algorithm, Hasher, HmacKey};
+use crate::otp::{truncate, Encoding, Token};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The suite is not of the form `OCRA-1:HOTP-<hash>-<digits>:<data input>`.
+    InvalidSuite,
+    /// The challenge does not match the format or length of the suite.
+    InvalidChallenge,
+    /// The session information does not match the length of the suite.
+    InvalidSession,
+}
+
+/// Format of the challenge (question).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Challenge {
+    Numeric,
+    Alphanumeric,
+    Hex,
+}
+
+/// Parsed OCRA suite, e.g. `OCRA-1:HOTP-SHA1-6:QN08`.
+///
+/// Keeps a copy of the text, it is part of the data input, so the suite can be parsed once and
+/// stored next to the token.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Suite {
+    text: [u8; MAX_SUITE_SIZE],
+    len: u8,
+    pub algorithm: Algorithm,
+    pub digits: u8,
+    /// The data input contains a counter (`C`).
+    pub counter: bool,
+    pub challenge: Challenge,
+    /// Maximum length of the challenge.
+    pub challenge_length: u8,
+    /// Hash function of the PIN (`PSHA1`, ...).
+    pub pin: Option<Algorithm>,
+    /// Length of the session information in bytes (`S064`, ...).
+    pub session: Option<usize>,
+    /// Length of a time step in seconds (`T1M`, ...).
+    pub time_step: Option<u64>,
+}
+
+impl Suite {
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        if text.len() > MAX_SUITE_SIZE {
+            return Err(Error::InvalidSuite);
+        }
+        let mut parts = text.split(':');
+        let (version, function, data_input) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(version), Some(function), Some(data_input)) => (version, function, data_input),
+            _ => return Err(Error::InvalidSuite),
+        };
+        if version != "OCRA-1" || parts.next().is_some() {
+            return Err(Error::InvalidSuite);
+        }
+
+        let mut function = function.split('-');
+        if function.next() != Some("HOTP") {
+            return Err(Error::InvalidSuite);
+        }
+        let algorithm = match function.next() {
+            Some("SHA1") => Algorithm::Sha1,
+            Some("SHA256") => Algorithm::Sha256,
+            Some("SHA512") => Algorithm::Sha512,
+            _ => return Err(Error::InvalidSuite),
+        };
+        let digits = match function.next().map(u8::from_str) {
+            // a length of 0 (no truncation) is not supported
+            Some(Ok(digits @ 4..=10)) => digits,
+            _ => return Err(Error::InvalidSuite),
+        };
+        if function.next().is_some() {
+            return Err(Error::InvalidSuite);
+        }
+
+        let mut copy = [0u8; MAX_SUITE_SIZE];
+        copy[..text.len()].copy_from_slice(text.as_bytes());
+        let mut suite = Suite {
+            text: copy,
+            len: text.len() as u8,
+            algorithm,
+            digits,
+            counter: false,
+            challenge: Challenge::Numeric,
+            challenge_length: 0,
+            pin: None,
+            session: None,
+            time_step: None,
+        };
+        let mut data_input = data_input.split('-').peekable();
+        if data_input.peek() == Some(&"C") {
+            suite.counter = true;
+            data_input.next();
+        }
+        match data_input.next() {
+            Some(question) if question.len() == 4 && question.starts_with('Q') => {
+                suite.challenge = match question.get(1..2) {
+                    Some("N") => Challenge::Numeric,
+                    Some("A") => Challenge::Alphanumeric,
+                    Some("H") => Challenge::Hex,
+                    _ => return Err(Error::InvalidSuite),
+                };
+                suite.challenge_length = match question.get(2..).map(u8::from_str) {
+                    Some(Ok(length @ 4..=64)) => length,
+                    _ => return Err(Error::InvalidSuite),
+                };
+            }
+            _ => return Err(Error::InvalidSuite),
+        }
+        for input in data_input {
+            // the optional inputs have to be in the order P, S, T
+            match (input.get(..1), input.get(1..)) {
+                (Some("P"), Some(hash))
+                    if suite.pin.is_none()
+                        && suite.session.is_none()
+                        && suite.time_step.is_none() =>
+                {
+                    suite.pin = Some(match hash {
+                        "SHA1" => Algorithm::Sha1,
+                        "SHA256" => Algorithm::Sha256,
+                        "SHA512" => Algorithm::Sha512,
+                        _ => return Err(Error::InvalidSuite),
+                    });
+                }
+                (Some("S"), Some(length))
+                    if suite.session.is_none() && suite.time_step.is_none() =>
+                {
+                    suite.session = match length.parse() {
+                        Ok(length) if length <= MAX_SESSION_SIZE => Some(length),
+                        _ => return Err(Error::InvalidSuite),
+                    };
+                }
+                (Some("T"), Some(step))
+                    if suite.time_step.is_none() && step.len() >= 2 && step.is_ascii() =>
+                {
+                    let (value, unit) = step.split_at(step.len() - 1);
+                    let value: u64 = value.parse().map_err(|_| Error::InvalidSuite)?;
+                    suite.time_step = Some(match (unit, value) {
+                        ("S", 1..=59) => value,
+                        ("M", 1..=59) => value * 60,
+                        ("H", 1..=48) => value * 60 * 60,
+                        _ => return Err(Error::InvalidSuite),
+                    });
+                }
+                _ => return Err(Error::InvalidSuite),
+            }
+        }
+        Ok(suite)
+    }
+
+    pub fn as_str(&self) -> &str {
+        // copied from a str by parse
+        core::str::from_utf8(&self.text[..self.len as usize]).unwrap_or_default()
+    }
+}
+
+impl fmt::Debug for Suite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Suite").field(&self.as_str()).finish()
+    }
+}
+
+/// Values of the data input, only the ones required by the suite are used.
+#[derive(Default)]
+pub struct Input<'a> {
+    pub counter: u64,
+    pub challenge: &'a str,
+    /// The plain PIN, it is hashed as specified by the suite.
+    pub pin: &'a str,
+    pub session: &'a [u8],
+    pub timestamp: u64,
+}
+
+const MAX_SUITE_SIZE: usize = 64;
+const QUESTION_SIZE: usize = 128;
+const MAX_SESSION_SIZE: usize = 512;
+const MAX_DATA_INPUT_SIZE: usize =
+    MAX_SUITE_SIZE + 1 + 8 + QUESTION_SIZE + 64 + MAX_SESSION_SIZE + 8;
+
+/// Computes the OCRA response of `suite` for `input`, `key` has to use the hash function of the
+/// suite.
+pub fn ocra<H: Hasher + ?Sized>(
+    hasher: &mut H,
+    suite: &Suite,
+    key: &HmacKey,
+    input: &Input,
+) -> Result<Token, Error> {
+    let mut data = [0u8; MAX_DATA_INPUT_SIZE];
+    let mut len = 0;
+    let mut push = |bytes: &[u8]| {
+        data[len..len + bytes.len()].copy_from_slice(bytes);
+        len += bytes.len();
+    };
+
+    // the suite is separated from the data input by a zero byte
+    push(suite.as_str().as_bytes());
+    push(&[0]);
+    if suite.counter {
+        push(&input.counter.to_be_bytes());
+    }
+    push(&question(suite, input.challenge)?);
+    if let Some(algorithm) = suite.pin {
+        push(&hasher.digest(algorithm, &[input.pin.as_bytes()]));
+    }
+    if let Some(size) = suite.session {
+        if input.session.len() != size {
+            return Err(Error::InvalidSession);
+        }
+        push(input.session);
+    }
+    let mut valid_until = u64::MAX;
+    if let Some(step) = suite.time_step {
+        let t = input.timestamp / step;
+        push(&t.to_be_bytes());
+        valid_until = (t + 1) * step;
+    }
+
+    let hmac = key.sign(hasher, &data[..len]);
+    Ok(Token {
+        code: truncate(&hmac) as u64 % 10u64.pow(suite.digits as u32),
+        digits: suite.digits,
+        encoding: Encoding::Decimal,
+        valid_until,
+    })
+}
+
+/// Encodes the challenge into the 128 byte question of the data input.
+fn question(suite: &Suite, challenge: &str) -> Result<[u8; QUESTION_SIZE], Error> {
+    if challenge.is_empty() || challenge.len() > suite.challenge_length as usize {
+        return Err(Error::InvalidChallenge);
+    }
+    let mut question = [0u8; QUESTION_SIZE];
+    match suite.challenge {
+        Challenge::Alphanumeric => {
+            if !challenge.bytes().all(|b| b.is_ascii_alphanumeric()) {
+                return Err(Error::InvalidChallenge);
+            }
+            question[..challenge.len()].copy_from_slice(challenge.as_bytes());
+        }
+        Challenge::Hex => {
+            for (i, c) in challenge.chars().enumerate() {
+                let nibble = c.to_digit(16).ok_or(Error::InvalidChallenge)?;
+                set_nibble(&mut question, i, nibble as u8);
+            }
+        }
+        Challenge::Numeric => {
+            // the decimal number is converted to hex (without leading zeros) and left aligned
+            let mut value = [0u8; 27]; // 10^64 < 2^216
+            for c in challenge.chars() {
+                let mut carry = c.to_digit(10).ok_or(Error::InvalidChallenge)?;
+                for b in value.iter_mut().rev() {
+                    let v = *b as u32 * 10 + carry;
+                    *b = v as u8;
+                    carry = v >> 8;
+                }
+            }
+            let nibbles = value.iter().flat_map(|b| [b >> 4, b & 0x0F]);
+            let leading_zeros = nibbles.clone().take_while(|n| *n == 0).count();
+            for (i, nibble) in nibbles.skip(leading_zeros).enumerate() {
+                set_nibble(&mut question, i, nibble);
+            }
+        }
+    }
+    Ok(question)
+}
+
+fn set_nibble(buffer: &mut [u8], index: usize, nibble: u8) {
+    match index % 2 {
+        0 => buffer[index / 2] |= nibble << 4,
+        _ => buffer[index / 2] |= nibble,
+    }
+}
@@ -0,0 +1,257 @@
+use core::fmt;
+use core::fmt::Write;
+
+use md5::{Digest, Md5};
+
+use crate::hash::{Algorithm, Hasher, HmacKey};
+
+/// How the code of a [`Token`] is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Decimal,
+    /// Steam Guard alphabet, least significant symbol first.
+    Steam,
+    /// Lower case hexadecimal.
+    Hex,
+    /// Lower case latin letters, most significant letter first.
+    Latin,
+}
+
+pub struct Token {
+    pub code: u64,
+    /// Number of digits or symbols of the code.
+    pub digits: u8,
+    pub encoding: Encoding,
+    pub valid_until: u64,
+}
+
+impl Token {
+    /// Whether `code` is the written form of this token.
+    pub fn matches(&self, code: &str) -> bool {
+        let mut matcher = Matcher(code);
+        write!(matcher, "{}", self).is_ok() && matcher.0.is_empty()
+    }
+}
+
+/// Compares formatted text with the expected text, without allocating.
+struct Matcher<'a>(&'a str);
+
+impl Write for Matcher<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 = self.0.strip_prefix(s).ok_or(fmt::Error)?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.encoding {
+            Encoding::Decimal => {
+                write!(f, "{:0width$}", self.code, width = self.digits as usize)
+            }
+            Encoding::Steam => {
+                let mut code = self.code;
+                for _ in 0..self.digits {
+                    let symbol = STEAM_ALPHABET[(code % STEAM_ALPHABET.len() as u64) as usize];
+                    write!(f, "{}", symbol as char)?;
+                    code /= STEAM_ALPHABET.len() as u64;
+                }
+                Ok(())
+            }
+            Encoding::Hex => {
+                write!(f, "{:0width$x}", self.code, width = self.digits as usize)
+            }
+            Encoding::Latin => {
+                for i in (0..self.digits as u32).rev() {
+                    let letter = b'a' + (self.code / 26u64.pow(i) % 26) as u8;
+                    write!(f, "{}", letter as char)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Dynamic truncation of RFC 4226, returns 31 bits of the HMAC.
+pub fn truncate(hmac: &[u8]) -> u32 {
+    let offset = (hmac.last().unwrap() & 0x0F) as usize;
+    u32::from_be_bytes(hmac[offset..=offset + 3].try_into().unwrap()) & 0x7fff_ffff
+}
+
+/// HOTP (RFC 4226), without truncation to the number of digits.
+pub fn hotp_value<H: Hasher + ?Sized>(hasher: &mut H, key: &HmacKey, counter: u64) -> u32 {
+    truncate(&key.sign(hasher, &counter.to_be_bytes()))
+}
+
+/// HOTP (RFC 4226) code for `counter`, the hash function is the one of `key`.
+///
+/// HOTP codes do not expire, they are valid until the next counter value was used.
+pub fn hotp<H: Hasher + ?Sized>(hasher: &mut H, key: &HmacKey, digits: u8, counter: u64) -> Token {
+    let value = hotp_value(hasher, key, counter);
+    Token {
+        code: value as u64 % 10u64.pow(digits as u32),
+        digits,
+        encoding: Encoding::Decimal,
+        valid_until: u64::MAX,
+    }
+}
+
+/// Moves the time of a token by a custom epoch `t0` (RFC 6238) and a known clock `offset` in
+/// seconds, e.g. of a server with a skewed clock.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimeShift {
+    pub t0: u64,
+    pub offset: i64,
+}
+
+impl TimeShift {
+    /// Converts the unix `timestamp` into the time the time step is computed from.
+    pub fn apply(&self, timestamp: u64) -> u64 {
+        timestamp
+            .saturating_add_signed(self.offset)
+            .saturating_sub(self.t0)
+    }
+
+    /// Converts a time of the token, e.g. `valid_until`, back into a unix timestamp. `u64::MAX`,
+    /// i.e. a token which does not expire, is kept.
+    pub fn revert(&self, time: u64) -> u64 {
+        match time {
+            u64::MAX => u64::MAX,
+            time => time
+                .saturating_add(self.t0)
+                .saturating_add_signed(self.offset.saturating_neg()),
+        }
+    }
+
+    /// Generates a token for the shifted `timestamp`, `valid_until` is a unix timestamp again.
+    pub fn token(&self, timestamp: u64, generate: impl FnOnce(u64) -> Token) -> Token {
+        let token = generate(self.apply(timestamp));
+        Token {
+            valid_until: self.revert(token.valid_until),
+            ..token
+        }
+    }
+}
+
+/// TOTP (RFC 6238) code for the unix `timestamp`.
+pub fn totp<H: Hasher + ?Sized>(
+    hasher: &mut H,
+    key: &HmacKey,
+    digits: u8,
+    period: u64,
+    timestamp: u64,
+) -> Token {
+    let t = timestamp / period;
+    Token {
+        valid_until: (t + 1) * period,
+        ..hotp(hasher, key, digits, t)
+    }
+}
+
+/// Checks `code` against the time steps up to `window` steps before and after the one of
+/// `timestamp`, the tokens are created by `generate` for a timestamp in the respective step. A
+/// step without a token (`None`) never matches.
+///
+/// Returns the offset of the matching time step, the current step is checked first.
+pub fn verify(
+    code: &str,
+    timestamp: u64,
+    period: u64,
+    window: u64,
+    mut generate: impl FnMut(u64) -> Option<Token>,
+) -> Option<i64> {
+    let offsets = core::iter::once(0).chain((1..=window as i64).flat_map(|i| [-i, i]));
+    for offset in offsets {
+        let timestamp = match timestamp.checked_add_signed(offset * period as i64) {
+            Some(timestamp) => timestamp,
+            None => continue,
+        };
+        if generate(timestamp).is_some_and(|token| token.matches(code)) {
+            return Some(offset);
+        }
+    }
+    None
+}
+
+const STEAM_ALPHABET: &[u8; 26] = b"23456789BCDFGHJKMNPQRTVWXY";
+pub const STEAM_DIGITS: u8 = 5;
+pub const STEAM_PERIOD: u64 = 30;
+
+/// Steam Guard code, a TOTP with a period of 30 seconds written with 5 symbols of the Steam
+/// alphabet. Steam always uses SHA1, i.e. `key` has to be a SHA1 key.
+pub fn steam<H: Hasher + ?Sized>(hasher: &mut H, key: &HmacKey, timestamp: u64) -> Token {
+    let t = timestamp / STEAM_PERIOD;
+    let value = hotp_value(hasher, key, t);
+    Token {
+        code: value as u64 % (STEAM_ALPHABET.len() as u64).pow(STEAM_DIGITS as u32),
+        digits: STEAM_DIGITS,
+        encoding: Encoding::Steam,
+        valid_until: (t + 1) * STEAM_PERIOD,
+    }
+}
+
+pub const MOTP_DIGITS: u8 = 6;
+pub const MOTP_PERIOD: u64 = 10;
+
+/// Feeds formatted text into a hash function.
+struct HashWriter<'a, D>(&'a mut D);
+
+impl<D: Digest> Write for HashWriter<'_, D> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.update(s.as_bytes());
+        Ok(())
+    }
+}
+
+/// Mobile-OTP code, the first 6 hex digits of the MD5 of the time step, the lower case hex
+/// encoded `key` and the `pin`.
+pub fn motp(key: &[u8], pin: &str, timestamp: u64) -> Token {
+    let t = timestamp / MOTP_PERIOD;
+    let mut md5 = Md5::new();
+    let mut writer = HashWriter(&mut md5);
+    write!(writer, "{}", t).unwrap();
+    for b in key {
+        write!(writer, "{:02x}", b).unwrap();
+    }
+    writer.write_str(pin).unwrap();
+    let hash = md5.finalize();
+    Token {
+        code: u64::from_be_bytes([0, 0, 0, 0, 0, hash[0], hash[1], hash[2]]),
+        digits: MOTP_DIGITS,
+        encoding: Encoding::Hex,
+        valid_until: (t + 1) * MOTP_PERIOD,
+    }
+}
+
+pub const YANDEX_DIGITS: u8 = 8;
+pub const YANDEX_PERIOD: u64 = 30;
+/// Length of the secret, Yandex Key secrets may be followed by a checksum which is not used.
+const YANDEX_SECRET_SIZE: usize = 16;
+
+/// HMAC-SHA256 key of a Yandex Key token, the SHA256 of `pin` and `secret`.
+pub fn yandex_key<H: Hasher + ?Sized>(hasher: &mut H, secret: &[u8], pin: &str) -> HmacKey {
+    let secret = &secret[..secret.len().min(YANDEX_SECRET_SIZE)];
+    let key_hash = hasher.digest(Algorithm::Sha256, &[pin.as_bytes(), secret]);
+    // a leading zero byte is dropped from the key
+    let key_hash = match key_hash[0] {
+        0 => &key_hash[1..],
+        _ => &key_hash[..],
+    };
+    HmacKey::new(hasher, Algorithm::Sha256, key_hash)
+}
+
+/// Yandex Key code, a TOTP with the key of [`yandex_key`], truncated to 63 bits and written with 8
+/// latin letters.
+pub fn yandex<H: Hasher + ?Sized>(hasher: &mut H, key: &HmacKey, timestamp: u64) -> Token {
+    let t = timestamp / YANDEX_PERIOD;
+    let hmac = key.sign(hasher, &t.to_be_bytes());
+    let offset = (hmac.last().unwrap() & 0x0F) as usize;
+    let value =
+        u64::from_be_bytes(hmac[offset..offset + 8].try_into().unwrap()) & 0x7fff_ffff_ffff_ffff;
+    Token {
+        code: value % 26u64.pow(YANDEX_DIGITS as u32),
+        digits: YANDEX_DIGITS,
+        encoding: Encoding::Latin,
+        valid_until: (t + 1) * YANDEX_PERIOD,
+    }
+}
@@ -0,0 +1,194 @@
+//! `otpauth://` URIs as handed out by the services, see
+//! <https://github.com/google/google-authenticator/wiki/Key-Uri-Format>.
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use data_encoding::BASE32_NOPAD;
+
+use crate::algorithm::{self, Trigger};
+use crate::hash::Algorithm;
+use crate::vault::Token;
+
+const SCHEME: &str = "otpauth://";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The URI does not start with `otpauth://`.
+    InvalidScheme,
+    /// Neither an account name nor an issuer.
+    MissingLabel,
+    MissingSecret,
+    UnknownType(String),
+    /// Invalid percent-encoding or not UTF-8.
+    InvalidEncoding,
+    /// The value of the parameter can not be parsed.
+    InvalidParameter(&'static str),
+    /// The token has a `t0` or `offset_seconds`, which can not be given in a URI.
+    UnsupportedTimeShift,
+}
+
+/// Parses `otpauth://TYPE/[ISSUER:]ACCOUNT?secret=...`, the token type is any name of
+/// [`algorithm::find`].
+///
+/// The `secret` is normalized to upper case base32 without padding and becomes the `key` of the
+/// token, the `issuer` parameter takes precedence over the one of the label. Unknown parameters
+/// are ignored.
+pub fn parse(uri: &str) -> Result<Token, Error> {
+    let rest = match uri.get(..SCHEME.len()) {
+        Some(scheme) if scheme.eq_ignore_ascii_case(SCHEME) => &uri[SCHEME.len()..],
+        _ => return Err(Error::InvalidScheme),
+    };
+    let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let (kind, label) = path.split_once('/').ok_or(Error::MissingLabel)?;
+    let kind = kind.to_ascii_lowercase();
+    if algorithm::find(&kind).is_none() {
+        return Err(Error::UnknownType(kind));
+    }
+
+    let (issuer, name) = match label.split_once(':') {
+        Some((issuer, name)) => (decode(issuer)?, decode(name)?),
+        // the separator may be escaped as well
+        None => {
+            let label = decode(label)?;
+            let (issuer, name) = split_label(&label);
+            (issuer.unwrap_or_default().into(), name.into())
+        }
+    };
+    let mut token = Token::new(name.trim().into(), Vec::new());
+    token.kind = kind;
+    token.issuer = Some(issuer.trim())
+        .filter(|issuer| !issuer.is_empty())
+        .map(Into::into);
+
+    for parameter in query.split('&').filter(|parameter| !parameter.is_empty()) {
+        let (key, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+        let value = decode(value)?;
+        match key {
+            "secret" => {
+                token.key = normalize_secret(&value).ok_or(Error::InvalidParameter("secret"))?
+            }
+            "issuer" if !value.is_empty() => token.issuer = Some(value),
+            "algorithm" => {
+                token.algorithm =
+                    Algorithm::from_name(&value).ok_or(Error::InvalidParameter("algorithm"))?
+            }
+            "digits" => token.digits = parse_number(&value, "digits")?,
+            "period" => token.period = parse_number(&value, "period")?,
+            "counter" => token.counter = parse_number(&value, "counter")?,
+            "suite" => token.suite = Some(value),
+            _ => {}
+        }
+    }
+
+    if token.key.is_empty() {
+        return Err(Error::MissingSecret);
+    }
+    if token.name.is_empty() {
+        token.name = token.issuer.clone().ok_or(Error::MissingLabel)?;
+    }
+    Ok(token)
+}
+
+/// The URI of the token with its decrypted `secret`, [`parse`] returns the same token.
+///
+/// The `period` is only given for time based and the `counter` for counter based tokens, the
+/// `suite` for OCRA tokens. Tokens shifted by `t0` or `offset_seconds` are rejected, other apps
+/// would show different codes.
+pub fn to_uri(token: &Token) -> Result<String, Error> {
+    if token.is_time_shifted() {
+        return Err(Error::UnsupportedTimeShift);
+    }
+    let mut uri = String::from(SCHEME);
+    uri.push_str(&token.kind);
+    uri.push('/');
+    if let Some(issuer) = &token.issuer {
+        uri.push_str(&encode(issuer));
+        uri.push(':');
+    }
+    uri.push_str(&encode(&token.name));
+    uri.push_str("?secret=");
+    uri.push_str(&BASE32_NOPAD.encode(&token.secret));
+    if let Some(issuer) = &token.issuer {
+        uri.push_str("&issuer=");
+        uri.push_str(&encode(issuer));
+    }
+    write!(
+        uri,
+        "&algorithm={}&digits={}",
+        token.algorithm.name(),
+        token.digits
+    )
+    .unwrap();
+    match token.otp().trigger() {
+        Trigger::Time => write!(uri, "&period={}", token.time_step()).unwrap(),
+        Trigger::Counter => write!(uri, "&counter={}", token.counter).unwrap(),
+        Trigger::Challenge => {}
+    }
+    if let Some(suite) = &token.suite {
+        uri.push_str("&suite=");
+        uri.push_str(&encode(suite));
+    }
+    Ok(uri)
+}
+
+/// Splits `ISSUER:ACCOUNT` into the (non-empty) issuer and the account.
+pub(crate) fn split_label(label: &str) -> (Option<&str>, &str) {
+    match label.split_once(':') {
+        Some((issuer, account)) => {
+            let issuer = issuer.trim();
+            ((!issuer.is_empty()).then_some(issuer), account.trim())
+        }
+        None => (None, label.trim()),
+    }
+}
+
+/// Upper case base32 without padding and spaces, the secrets are often shown in groups. `None` if
+/// it is not valid base32.
+pub fn normalize_secret(secret: &str) -> Option<String> {
+    let secret: String = secret
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '='))
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    BASE32_NOPAD.decode(secret.as_bytes()).ok()?;
+    Some(secret)
+}
+
+fn parse_number<T: core::str::FromStr>(value: &str, name: &'static str) -> Result<T, Error> {
+    value.parse().map_err(|_| Error::InvalidParameter(name))
+}
+
+/// Escapes all but the unreserved characters of RFC 3986 as `%XX`.
+pub(crate) fn encode(component: &str) -> String {
+    let mut encoded = String::with_capacity(component.len());
+    for byte in component.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            write!(encoded, "%{:02X}", byte).unwrap();
+        }
+    }
+    encoded
+}
+
+/// Decodes the `%XX` escapes of a component of the URI.
+pub(crate) fn decode(component: &str) -> Result<String, Error> {
+    let mut bytes = Vec::with_capacity(component.len());
+    let mut rest = component.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = tail
+                .get(..2)
+                .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+                .and_then(|hex| core::str::from_utf8(hex).ok())
+                .ok_or(Error::InvalidEncoding)?;
+            bytes.push(u8::from_str_radix(hex, 16).map_err(|_| Error::InvalidEncoding)?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| Error::InvalidEncoding)
+}
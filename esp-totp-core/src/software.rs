@@ -0,0 +1,28 @@
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+
+use crate::hash::{Algorithm, Digest, Hasher};
+
+/// [`Hasher`] implemented with the RustCrypto hash functions.
+#[derive(Default)]
+pub struct Software;
+
+impl Hasher for Software {
+    fn digest(&mut self, algorithm: Algorithm, parts: &[&[u8]]) -> Digest {
+        fn run<D: digest::Digest>(parts: &[&[u8]], output: &mut [u8]) {
+            let mut hasher = D::new();
+            for part in parts {
+                hasher.update(part);
+            }
+            output.copy_from_slice(&hasher.finalize());
+        }
+
+        let mut output = Digest::new(algorithm);
+        match algorithm {
+            Algorithm::Sha1 => run::<Sha1>(parts, output.as_mut_slice()),
+            Algorithm::Sha256 => run::<Sha256>(parts, output.as_mut_slice()),
+            Algorithm::Sha512 => run::<Sha512>(parts, output.as_mut_slice()),
+        }
+        output
+    }
+}
@@ -0,0 +1,643 @@
+//! Format of the config on the SD card (`CFG`), shared by the firmware and the host tools.
+//!
+//! The config is JSON, the keys of the tokens are encrypted with a key derived from the PIN and
+//! base32 encoded.
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use aes::cipher::{KeyIvInit, StreamCipher};
+use aes_gcm::aead::{AeadInPlace, KeyInit};
+use aes_gcm::{Aes128Gcm, Nonce, Tag};
+use data_encoding::BASE32_NOPAD;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::algorithm::{self, OtpAlgorithm, Trigger};
+use crate::hash::{Algorithm, Hasher, HmacKey};
+use crate::kdf::pbkdf2;
+use crate::ocra::Suite;
+use crate::otp::{self, TimeShift};
+use crate::{migration, otpauth};
+
+/// Version of the config format, configs without a `version` are version 0.
+pub const VERSION: u64 = 1;
+
+/// Number of digits of the PIN of the config, entered when the device starts.
+pub const PIN_LENGTH: usize = 6;
+
+/// The PIN of a token is entered in one line of the display.
+pub const MAX_PIN_LENGTH: u8 = 16;
+
+/// File next to the config with the [`Counters`] written back by the device.
+pub const COUNTER_FILE: &str = "CTR";
+
+/// Length of a new salt of the [`Kdf`].
+pub const SALT_SIZE: usize = 16;
+
+const KEY_SIZE: usize = 16;
+const CTR_IV_SIZE: usize = 16;
+const GCM_NONCE_SIZE: usize = 12;
+const GCM_TAG_SIZE: usize = 16;
+
+type Aes128Ctr64LE = ctr::Ctr64LE<aes::Aes128>;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Token {
+    pub name: String,
+    /// Service of the account, e.g. from an otpauth URI.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issuer: Option<String>,
+    pub key: String,
+    /// Length of a TOTP time step in seconds.
+    #[serde(default = "default_period")]
+    pub period: u64,
+    /// Number of digits of the generated code.
+    #[serde(default = "default_digits")]
+    pub digits: u8,
+    /// Hash function used for the HMAC.
+    #[serde(default)]
+    pub algorithm: Algorithm,
+    /// Name of the token type, see [`algorithm::find`].
+    #[serde(rename = "type", default = "default_kind")]
+    pub kind: String,
+    /// Counter of the next HOTP code.
+    #[serde(default)]
+    pub counter: u64,
+    /// Number of digits of the mOTP, Yandex or OCRA PIN.
+    #[serde(default = "default_pin_length")]
+    pub pin_length: u8,
+    /// PIN of a mOTP, Yandex or OCRA token, entered on the device.
+    #[serde(skip)]
+    pub pin: Option<String>,
+    /// OCRA suite, e.g. `OCRA-1:HOTP-SHA1-6:QN08`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suite: Option<String>,
+    /// Unix time the time steps are counted from (T0 of RFC 6238).
+    #[serde(default)]
+    pub t0: u64,
+    /// Seconds added to the time of the RTC, for servers with a known skew.
+    #[serde(default)]
+    pub offset_seconds: i64,
+    /// Number of time steps before and after the current one in which a verified code is accepted.
+    #[serde(default = "default_verify_window")]
+    pub verify_window: u8,
+    /// The `key` is the unencrypted base32 secret, e.g. of an otpauth URI. It is encrypted with
+    /// the others by [`Config::encrypt`].
+    #[serde(default, skip_serializing_if = "core::ops::Not::not")]
+    pub plaintext: bool,
+    /// The decoded and decrypted `key`.
+    #[serde(skip)]
+    pub secret: Vec<u8>,
+    /// HMAC key prepared from `secret` by [`Token::prepare`].
+    #[serde(skip)]
+    pub hmac_key: HmacKey,
+    /// `suite` parsed by [`Token::parse_suite`].
+    #[serde(skip)]
+    parsed_suite: Option<Suite>,
+}
+
+impl fmt::Debug for Token {
+    /// Leaves out the decrypted secret, the PIN and the HMAC key.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Token")
+            .field("name", &self.name)
+            .field("issuer", &self.issuer)
+            .field("key", &self.key)
+            .field("period", &self.period)
+            .field("digits", &self.digits)
+            .field("algorithm", &self.algorithm)
+            .field("kind", &self.kind)
+            .field("counter", &self.counter)
+            .field("pin_length", &self.pin_length)
+            .field("suite", &self.suite)
+            .field("t0", &self.t0)
+            .field("offset_seconds", &self.offset_seconds)
+            .field("verify_window", &self.verify_window)
+            .field("plaintext", &self.plaintext)
+            .finish_non_exhaustive()
+    }
+}
+
+fn default_kind() -> String {
+    "totp".into()
+}
+
+fn default_period() -> u64 {
+    30
+}
+
+fn default_digits() -> u8 {
+    6
+}
+
+fn default_pin_length() -> u8 {
+    4
+}
+
+fn default_verify_window() -> u8 {
+    1
+}
+
+impl Token {
+    /// TOTP token with the default parameters, the `secret` is encrypted by [`Config::encrypt`].
+    pub fn new(name: String, secret: Vec<u8>) -> Self {
+        Token {
+            name,
+            issuer: None,
+            key: String::new(),
+            period: default_period(),
+            digits: default_digits(),
+            algorithm: Algorithm::default(),
+            kind: default_kind(),
+            counter: 0,
+            pin_length: default_pin_length(),
+            pin: None,
+            suite: None,
+            t0: 0,
+            offset_seconds: 0,
+            verify_window: default_verify_window(),
+            plaintext: false,
+            secret,
+            hmac_key: HmacKey::default(),
+            parsed_suite: None,
+        }
+    }
+
+    /// Checks the fields of the token, done for all tokens when the config is loaded.
+    pub fn validate(&self) -> Result<()> {
+        if self.period == 0 {
+            return Err(Error::InvalidPeriod(self.name.clone()));
+        }
+        if !matches!(self.digits, 6 | 7 | 8 | 10) {
+            return Err(Error::InvalidDigits(self.name.clone()));
+        }
+        if self.pin_length == 0 || self.pin_length > MAX_PIN_LENGTH {
+            return Err(Error::InvalidPinLength(self.name.clone()));
+        }
+        match algorithm::find(&self.kind) {
+            None => Err(Error::UnknownType(self.name.clone())),
+            Some(otp) => otp.validate(self),
+        }
+    }
+
+    /// The token type, it is checked when the config is loaded.
+    pub fn otp(&self) -> &'static dyn OtpAlgorithm {
+        algorithm::find(&self.kind).unwrap_or(algorithm::DEFAULT)
+    }
+
+    /// Length of a time step in seconds, fixed for some token types.
+    pub fn time_step(&self) -> u64 {
+        self.otp().time_step(self)
+    }
+
+    pub fn time_shift(&self) -> TimeShift {
+        TimeShift {
+            t0: self.t0,
+            offset: self.offset_seconds,
+        }
+    }
+
+    /// Whether the codes depend on the `t0` or `offset_seconds`, which other apps do not support.
+    pub fn is_time_shifted(&self) -> bool {
+        let uses_time = match self.otp().trigger() {
+            Trigger::Time => true,
+            Trigger::Counter => false,
+            Trigger::Challenge => self
+                .ocra_suite()
+                .is_some_and(|suite| suite.time_step.is_some()),
+        };
+        uses_time && (self.t0 != 0 || self.offset_seconds != 0)
+    }
+
+    /// The parsed OCRA suite, see [`Token::parse_suite`].
+    pub fn ocra_suite(&self) -> Option<&Suite> {
+        self.parsed_suite.as_ref()
+    }
+
+    /// Parses the `suite` once instead of on every code, done when the config is loaded and
+    /// again after the `suite` was changed.
+    pub fn parse_suite(&mut self) {
+        self.parsed_suite = self
+            .suite
+            .as_deref()
+            .and_then(|suite| Suite::parse(suite).ok());
+    }
+
+    /// Whether a PIN has to be entered on the device before a code can be generated.
+    pub fn needs_pin(&self) -> bool {
+        self.otp().needs_pin(self)
+    }
+
+    /// Prepares the HMAC key from the decrypted `secret`, again when the `pin` was entered.
+    pub fn prepare(&mut self, hasher: &mut dyn Hasher) {
+        self.hmac_key = self.otp().hmac_key(hasher, self);
+    }
+
+    /// Code for the unix `timestamp`, `challenge` is only used by challenge-response tokens.
+    ///
+    /// Does not allocate, the key has to be prepared with [`Token::prepare`].
+    pub fn code(
+        &self,
+        hasher: &mut dyn Hasher,
+        timestamp: u64,
+        challenge: &str,
+    ) -> core::result::Result<otp::Token, algorithm::Error> {
+        let shift = self.time_shift();
+        let token = self
+            .otp()
+            .code(hasher, self, shift.apply(timestamp), challenge)?;
+        Ok(otp::Token {
+            valid_until: shift.revert(token.valid_until),
+            ..token
+        })
+    }
+}
+
+/// Encryption of the token keys.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Cipher {
+    /// Unauthenticated, every PIN decrypts the keys. Uses the `nonce` of the config as IV.
+    #[default]
+    #[serde(rename = "aes-128-ctr")]
+    Aes128Ctr,
+    /// Authenticated, a wrong PIN is detected. Every key starts with its own 12 byte nonce and
+    /// ends with the 16 byte tag.
+    #[serde(rename = "aes-128-gcm")]
+    Aes128Gcm,
+}
+
+/// Derivation of the encryption key from the PIN with PBKDF2-HMAC-SHA256.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Kdf {
+    /// Base32 encoded random salt.
+    pub salt: String,
+    pub iterations: u32,
+}
+
+impl Kdf {
+    /// Kdf with a new salt, `random` fills a buffer with random bytes.
+    pub fn new(iterations: u32, random: &mut dyn FnMut(&mut [u8])) -> Self {
+        let mut salt = [0u8; SALT_SIZE];
+        random(&mut salt);
+        Kdf {
+            salt: BASE32_NOPAD.encode(&salt),
+            iterations,
+        }
+    }
+}
+
+/// Counters of the tokens by name. They are stored apart from the config, so the file with the
+/// keys is not rewritten for every code.
+pub type Counters = BTreeMap<String, u64>;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Config {
+    /// Always [`VERSION`], older configs are migrated by [`migrate`].
+    pub version: u64,
+    pub cipher: Cipher,
+    /// Without it the PIN itself is the key, as in configs of older versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kdf: Option<Kdf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    pub tokens: Vec<Token>,
+}
+
+impl Config {
+    /// Empty config of the current version.
+    pub fn new(cipher: Cipher, kdf: Option<Kdf>) -> Self {
+        Config {
+            version: VERSION,
+            cipher,
+            kdf,
+            nonce: None,
+            tokens: Vec::new(),
+        }
+    }
+
+    /// Parses and validates a config of the current version, see [`migrate`].
+    pub fn from_value(raw: Value) -> Result<Self> {
+        let mut config: Config = serde_json::from_value(raw).map_err(Error::Deserialize)?;
+        config.validate()?;
+        for token in config.tokens.iter_mut() {
+            token.parse_suite();
+        }
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.kdf.as_ref().is_some_and(|kdf| kdf.iterations == 0) {
+            return Err(Error::InvalidKdf);
+        }
+        self.tokens.iter().try_for_each(Token::validate)
+    }
+
+    /// Decrypts the keys of all tokens into their `secret`, the tokens are left unchanged if one
+    /// of them fails.
+    pub fn decrypt<H: Hasher + ?Sized>(&mut self, hasher: &mut H, pin: &str) -> Result<()> {
+        let key = derive_key(hasher, self.kdf.as_ref(), pin.as_bytes())?;
+
+        let secrets = match self.cipher {
+            Cipher::Aes128Ctr => self.decrypt_ctr(&key)?,
+            Cipher::Aes128Gcm => self.decrypt_gcm(&key)?,
+        };
+        for (token, secret) in self.tokens.iter_mut().zip(secrets) {
+            token.secret = secret;
+        }
+        Ok(())
+    }
+
+    /// The counters of the tokens which use one, see [`COUNTER_FILE`].
+    pub fn counters(&self) -> Counters {
+        self.tokens
+            .iter()
+            .filter(|token| token.otp().uses_counter(token))
+            .map(|token| (token.name.clone(), token.counter))
+            .collect()
+    }
+
+    /// Continues with the stored `counters`. A counter is never decreased, a higher one in the
+    /// config, e.g. after a resynchronization, is kept.
+    pub fn apply_counters(&mut self, counters: &Counters) {
+        for token in self.tokens.iter_mut() {
+            if let Some(counter) = counters.get(&token.name) {
+                token.counter = token.counter.max(*counter);
+            }
+        }
+    }
+
+    /// Whether [`Config::encrypt`] should be called with the PIN the config was just decrypted
+    /// with, to encrypt the `plaintext` keys. Without authentication a wrong PIN would destroy
+    /// the encrypted keys, then only a config of plaintext keys is encrypted.
+    pub fn needs_encryption(&self) -> bool {
+        let plaintext = self.tokens.iter().filter(|token| token.plaintext).count();
+        plaintext > 0 && (self.cipher == Cipher::Aes128Gcm || plaintext == self.tokens.len())
+    }
+
+    fn decrypt_ctr(&self, key: &[u8; KEY_SIZE]) -> Result<Vec<Vec<u8>>> {
+        // a config of plaintext keys has no nonce yet
+        if self.tokens.iter().all(|token| token.plaintext) {
+            return self.tokens.iter().map(plaintext_secret).collect();
+        }
+        let iv = match &self.nonce {
+            None => return Err(Error::MissingIV),
+            Some(nonce) => {
+                let nonce = BASE32_NOPAD
+                    .decode(nonce.as_bytes())
+                    .map_err(Error::Base32)?;
+                if nonce.len() != CTR_IV_SIZE {
+                    return Err(Error::InvalidIV);
+                }
+                let mut iv = [0u8; CTR_IV_SIZE];
+                iv.copy_from_slice(nonce.as_slice());
+                iv
+            }
+        };
+
+        let mut cipher = Aes128Ctr64LE::new(key.into(), &iv.into());
+
+        let mut secrets = Vec::with_capacity(self.tokens.len());
+        for token in self.tokens.iter() {
+            // the keystream only covers the encrypted keys
+            if token.plaintext {
+                secrets.push(plaintext_secret(token)?);
+                continue;
+            }
+            let mut raw = BASE32_NOPAD
+                .decode(token.key.as_bytes())
+                .map_err(Error::Base32)?;
+            cipher.apply_keystream(&mut raw);
+            secrets.push(raw);
+        }
+        Ok(secrets)
+    }
+
+    fn decrypt_gcm(&self, key: &[u8; KEY_SIZE]) -> Result<Vec<Vec<u8>>> {
+        let cipher = Aes128Gcm::new(key.into());
+
+        let mut secrets = Vec::with_capacity(self.tokens.len());
+        let mut first = true;
+        for token in self.tokens.iter() {
+            if token.plaintext {
+                secrets.push(plaintext_secret(token)?);
+                continue;
+            }
+            let raw = BASE32_NOPAD
+                .decode(token.key.as_bytes())
+                .map_err(Error::Base32)?;
+            if raw.len() < GCM_NONCE_SIZE + GCM_TAG_SIZE {
+                return Err(Error::InvalidKey(token.name.clone()));
+            }
+            let (nonce, rest) = raw.split_at(GCM_NONCE_SIZE);
+            let (ciphertext, tag) = rest.split_at(rest.len() - GCM_TAG_SIZE);
+            let mut secret = ciphertext.to_vec();
+            cipher
+                .decrypt_in_place_detached(
+                    Nonce::from_slice(nonce),
+                    &[],
+                    &mut secret,
+                    Tag::from_slice(tag),
+                )
+                .map_err(|_| match first {
+                    // all keys are encrypted with the same PIN, only the first one tells it is wrong
+                    true => Error::WrongPin,
+                    false => Error::InvalidKey(token.name.clone()),
+                })?;
+            first = false;
+            secrets.push(secret);
+        }
+        Ok(secrets)
+    }
+
+    /// Encrypts the `secret` of all tokens into their `key`, with new nonces from `random`.
+    pub fn encrypt<H: Hasher + ?Sized>(
+        &mut self,
+        hasher: &mut H,
+        pin: &str,
+        random: &mut dyn FnMut(&mut [u8]),
+    ) -> Result<()> {
+        let key = derive_key(hasher, self.kdf.as_ref(), pin.as_bytes())?;
+
+        match self.cipher {
+            Cipher::Aes128Ctr => {
+                let mut iv = [0u8; CTR_IV_SIZE];
+                random(&mut iv);
+                let mut cipher = Aes128Ctr64LE::new(&key.into(), &iv.into());
+                for token in self.tokens.iter_mut() {
+                    let mut raw = token.secret.clone();
+                    cipher.apply_keystream(&mut raw);
+                    token.key = BASE32_NOPAD.encode(&raw);
+                }
+                self.nonce = Some(BASE32_NOPAD.encode(&iv));
+            }
+            Cipher::Aes128Gcm => {
+                let cipher = Aes128Gcm::new(&key.into());
+                for token in self.tokens.iter_mut() {
+                    let mut nonce = [0u8; GCM_NONCE_SIZE];
+                    random(&mut nonce);
+                    let mut ciphertext = token.secret.clone();
+                    let tag = cipher
+                        .encrypt_in_place_detached(Nonce::from_slice(&nonce), &[], &mut ciphertext)
+                        .map_err(|_| Error::InvalidKey(token.name.clone()))?;
+                    let mut raw = Vec::with_capacity(nonce.len() + ciphertext.len() + tag.len());
+                    raw.extend_from_slice(&nonce);
+                    raw.extend_from_slice(&ciphertext);
+                    raw.extend_from_slice(&tag);
+                    token.key = BASE32_NOPAD.encode(&raw);
+                }
+                self.nonce = None;
+            }
+        }
+        for token in self.tokens.iter_mut() {
+            token.plaintext = false;
+        }
+        Ok(())
+    }
+}
+
+/// The decoded `key` of a `plaintext` token.
+fn plaintext_secret(token: &Token) -> Result<Vec<u8>> {
+    let key = otpauth::normalize_secret(&token.key).ok_or(Error::InvalidKey(token.name.clone()))?;
+    BASE32_NOPAD.decode(key.as_bytes()).map_err(Error::Base32)
+}
+
+/// AES key of the `pin`, without a `kdf` the (at most 16 bytes of the) PIN itself.
+fn derive_key<H: Hasher + ?Sized>(
+    hasher: &mut H,
+    kdf: Option<&Kdf>,
+    pin: &[u8],
+) -> Result<[u8; KEY_SIZE]> {
+    let mut key = [0u8; KEY_SIZE];
+    match kdf {
+        None => {
+            for (key, pin) in key.iter_mut().zip(pin) {
+                *key = *pin;
+            }
+        }
+        Some(kdf) => {
+            let salt = BASE32_NOPAD
+                .decode(kdf.salt.as_bytes())
+                .map_err(Error::Base32)?;
+            pbkdf2(
+                hasher,
+                Algorithm::Sha256,
+                pin,
+                &salt,
+                kdf.iterations,
+                &mut key,
+            );
+        }
+    }
+    Ok(key)
+}
+
+/// Upgrades of the raw config, the one at index `i` upgrades version `i` to `i + 1`.
+const MIGRATIONS: [fn(&mut Value); VERSION as usize] = [migrate_v0];
+
+/// Version 1 requires the `cipher`, older configs were always encrypted with AES-128-CTR.
+fn migrate_v0(raw: &mut Value) {
+    if raw.get("cipher").is_none() {
+        raw["cipher"] = "aes-128-ctr".into();
+    }
+}
+
+/// Migrates `raw` (see [`migrate`]), expands the tokens given as otpauth URIs and parses the
+/// config. Returns whether the version was migrated, then `raw` should be written back.
+pub fn load(raw: &mut Value) -> Result<(Config, bool)> {
+    let migrated = migrate(raw)?;
+    expand_uris(raw)?;
+    Ok((Config::from_value(raw.clone())?, migrated))
+}
+
+/// Replaces the tokens given as `otpauth://` URI by the parsed token and those given as
+/// `otpauth-migration://` URI by the tokens of the payload. The secrets of such URIs are not
+/// encrypted, the tokens are marked as `plaintext`.
+fn expand_uris(raw: &mut Value) -> Result<()> {
+    let Some(entries) = raw.get_mut("tokens").and_then(Value::as_array_mut) else {
+        return Ok(());
+    };
+    let mut tokens = Vec::with_capacity(entries.len());
+    // index of the first entry of the part, to report an incomplete batch
+    let mut batches: Vec<(usize, migration::Batch)> = Vec::new();
+    for (i, entry) in entries.drain(..).enumerate() {
+        let Some(uri) = entry.as_str() else {
+            tokens.push(entry);
+            continue;
+        };
+        if uri.starts_with("otpauth-migration:") {
+            let payload = migration::parse(uri).map_err(|err| Error::InvalidMigration(i, err))?;
+            batches.push((i, payload.batch));
+            for mut token in payload.tokens {
+                token.plaintext = true;
+                tokens.push(serde_json::to_value(token).map_err(Error::Deserialize)?);
+            }
+        } else {
+            let mut token = otpauth::parse(uri).map_err(|err| Error::InvalidUri(i, err))?;
+            token.plaintext = true;
+            tokens.push(serde_json::to_value(token).map_err(Error::Deserialize)?);
+        }
+    }
+    *entries = tokens;
+
+    let parts: Vec<migration::Batch> = batches.iter().map(|(_, batch)| *batch).collect();
+    if let Err(err) = migration::check_batches(&parts) {
+        let index = batches
+            .iter()
+            .find(|(_, batch)| err == migration::Error::IncompleteBatch(batch.id))
+            .map_or(0, |(i, _)| *i);
+        return Err(Error::InvalidMigration(index, err));
+    }
+    Ok(())
+}
+
+/// Upgrades `raw` to [`VERSION`], returns whether it was changed.
+pub fn migrate(raw: &mut Value) -> Result<bool> {
+    // the migrations add fields, which panics on anything but an object
+    if !raw.is_object() {
+        return Err(Error::InvalidConfig);
+    }
+    let version = match raw.get("version") {
+        None => 0,
+        Some(version) => version.as_u64().ok_or(Error::InvalidVersion)?,
+    };
+    if version > VERSION {
+        return Err(Error::UnsupportedVersion(version));
+    }
+    for migration in MIGRATIONS[version as usize..].iter() {
+        migration(raw);
+    }
+    raw["version"] = VERSION.into();
+    Ok(version < VERSION)
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Deserialize(serde_json::Error),
+    /// The config is not a JSON object.
+    InvalidConfig,
+    MissingIV,
+    InvalidIV,
+    InvalidKdf,
+    Base32(data_encoding::DecodeError),
+    /// The PIN does not decrypt the first key.
+    WrongPin,
+    /// The PIN decrypts the first key but not the key of this token.
+    InvalidKey(String),
+    InvalidPeriod(String),
+    InvalidDigits(String),
+    InvalidPinLength(String),
+    InvalidSuite(String),
+    UnknownType(String),
+    /// The token at this index of `tokens` is not a valid otpauth URI.
+    InvalidUri(usize, otpauth::Error),
+    /// The entry at this index of `tokens` is not a valid Google Authenticator export, or not all
+    /// parts of its batch are given.
+    InvalidMigration(usize, migration::Error),
+    InvalidVersion,
+    /// The config was written for a newer firmware.
+    UnsupportedVersion(u64),
+}
+
+pub type Result<T> = core::result::Result<T, Error>;
@@ -0,0 +1,190 @@
+//! Import of Aegis exports.
+use aes_gcm::aead::{AeadInPlace, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use data_encoding::{BASE64, HEXLOWER};
+use esp_totp_core::import::aegis::{Backup, Error};
+use esp_totp_core::import::Reason;
+use esp_totp_core::vault::{Cipher, Config};
+use esp_totp_core::Algorithm;
+use serde_json::{json, Value};
+
+const PASSWORD: &str = "test";
+
+fn database() -> Value {
+    json!({
+        "version": 2,
+        "entries": [
+            {
+                "type": "totp",
+                "uuid": "3ae6f1ad-2e65-4ed2-a953-1ec0dff2386d",
+                "name": "alice@example.com",
+                "issuer": "Deno",
+                "note": "",
+                "favorite": false,
+                "icon": null,
+                "info": {"secret": "4SJHB4GSD43FZBAI7C2HLRJGPQ", "algo": "SHA256", "digits": 8, "period": 60}
+            },
+            {
+                "type": "hotp",
+                "name": "bob",
+                "issuer": "",
+                "info": {"secret": "GEZDGNBVGY3TQOJQ", "algo": "SHA1", "digits": 6, "counter": 5}
+            },
+            {
+                "type": "yandex",
+                "name": "carol",
+                "issuer": "Yandex",
+                "info": {"secret": "LA2V6KMCGYMWWVEW64RNP3JA3I", "algo": "SHA256", "digits": 8, "period": 30, "pin": "5239"}
+            },
+        ],
+        "groups": []
+    })
+}
+
+/// AES-256-GCM with the nonce and tag in the format of the export.
+fn encrypt(key: &[u8; 32], nonce: [u8; 12], data: &mut [u8]) -> Value {
+    let tag = Aes256Gcm::new(key.into())
+        .encrypt_in_place_detached(Nonce::from_slice(&nonce), b"", data)
+        .unwrap();
+    json!({"nonce": HEXLOWER.encode(&nonce), "tag": HEXLOWER.encode(&tag)})
+}
+
+/// Encrypted export with a biometric and a password slot, with cheap scrypt parameters.
+fn encrypted() -> Value {
+    let master_key = [0x42u8; 32];
+    let mut db = serde_json::to_vec(&database()).unwrap();
+    let params = encrypt(&master_key, [1; 12], &mut db);
+
+    let salt = [7u8; 32];
+    let mut slot_key = [0u8; 32];
+    let scrypt_params = scrypt::Params::new(10, 8, 1, 32).unwrap();
+    scrypt::scrypt(PASSWORD.as_bytes(), &salt, &scrypt_params, &mut slot_key).unwrap();
+    let mut key = master_key;
+    let key_params = encrypt(&slot_key, [2; 12], &mut key);
+
+    json!({
+        "version": 1,
+        "header": {
+            "slots": [
+                {"type": 2, "uuid": "", "key": "00", "key_params": {"nonce": "00", "tag": "00"}},
+                {
+                    "type": 1,
+                    "uuid": "a8325752-c1be-458a-9b3e-5e0a8154d9ec",
+                    "key": HEXLOWER.encode(&key),
+                    "key_params": key_params,
+                    "n": 1024,
+                    "r": 8,
+                    "p": 1,
+                    "salt": HEXLOWER.encode(&salt),
+                    "repaired": true,
+                    "is_backup": false
+                }
+            ],
+            "params": params
+        },
+        "db": BASE64.encode(&db)
+    })
+}
+
+fn parse(export: Value) -> Backup {
+    Backup::parse(&serde_json::to_vec(&export).unwrap()).unwrap()
+}
+
+#[test]
+fn plaintext() {
+    let backup = parse(json!({
+        "version": 1,
+        "header": {"slots": null, "params": null},
+        "db": database()
+    }));
+    assert!(!backup.is_encrypted());
+    let tokens = backup.import(None).unwrap().tokens;
+
+    assert_eq!(tokens[0].name, "alice@example.com");
+    assert_eq!(tokens[0].issuer.as_deref(), Some("Deno"));
+    assert_eq!(tokens[0].key, "4SJHB4GSD43FZBAI7C2HLRJGPQ");
+    assert_eq!(tokens[0].kind, "totp");
+    assert_eq!(tokens[0].algorithm, Algorithm::Sha256);
+    assert_eq!(tokens[0].digits, 8);
+    assert_eq!(tokens[0].period, 60);
+
+    assert_eq!(tokens[1].kind, "hotp");
+    assert_eq!(tokens[1].issuer, None);
+    assert_eq!(tokens[1].counter, 5);
+
+    assert_eq!(tokens[2].kind, "yandex");
+    assert_eq!(tokens[2].pin_length, 4);
+}
+
+#[test]
+fn valid_config() {
+    let mut db = database();
+    db["entries"].as_array_mut().unwrap().push(json!({
+        "type": "steam",
+        "name": "dave",
+        "issuer": "Steam",
+        "info": {"secret": "GEZDGNBVGY3TQOJQ", "algo": "SHA1", "digits": 5, "period": 30}
+    }));
+    let backup = parse(json!({"version": 1, "header": {"slots": null, "params": null}, "db": db}));
+    let report = backup.import(None).unwrap();
+    assert!(report.skipped.is_empty());
+    assert_eq!(report.tokens[3].kind, "steam");
+
+    let mut config = Config::new(Cipher::Aes128Gcm, None);
+    config.tokens = report.tokens;
+    config.validate().unwrap();
+}
+
+#[test]
+fn encrypted_export() {
+    let backup = parse(encrypted());
+    assert!(backup.is_encrypted());
+    let tokens = backup.import(Some(PASSWORD)).unwrap().tokens;
+    assert_eq!(tokens.len(), 3);
+    assert_eq!(tokens[0].key, "4SJHB4GSD43FZBAI7C2HLRJGPQ");
+
+    assert!(matches!(backup.import(None), Err(Error::PasswordRequired)));
+    assert!(matches!(
+        backup.import(Some("wrong")),
+        Err(Error::WrongPassword)
+    ));
+
+    let mut export = encrypted();
+    export["header"]["slots"].as_array_mut().unwrap().remove(1);
+    assert!(matches!(
+        parse(export).import(Some(PASSWORD)),
+        Err(Error::NoPasswordSlot)
+    ));
+}
+
+#[test]
+fn skipped_entries() {
+    let mut db = database();
+    db["entries"][0]["info"]["algo"] = "MD4".into();
+    db["entries"][1]["info"]["secret"] = "not base32!".into();
+    db["entries"][2]["type"] = "sms".into();
+    db["entries"].as_array_mut().unwrap().push(json!({
+        "type": "motp",
+        "name": "dave",
+        "info": {"secret": "ASNFPHRZ", "algo": "MD5", "digits": 6, "period": 10, "pin": "1234"}
+    }));
+    let backup = parse(json!({"version": 1, "header": {"slots": null, "params": null}, "db": db}));
+    let report = backup.import(None).unwrap();
+
+    assert_eq!(report.tokens.len(), 1);
+    assert_eq!(report.tokens[0].kind, "motp");
+    assert_eq!(
+        report.skipped,
+        [
+            (
+                "Deno: alice@example.com".into(),
+                Reason::UnsupportedAlgorithm("MD4".into())
+            ),
+            ("bob".into(), Reason::InvalidSecret),
+            (
+                "Yandex: carol".into(),
+                Reason::UnsupportedType("sms".into())
+            ),
+        ]
+    );
+}
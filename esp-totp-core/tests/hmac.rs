@@ -0,0 +1,62 @@
+//! Compares the HMAC construction used with the hardware hasher against the RustCrypto HMAC.
+use esp_totp_core::hash::hmac;
+use esp_totp_core::software::Software;
+use esp_totp_core::{Algorithm, HmacKey};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+
+fn reference<M: Mac + hmac::digest::KeyInit>(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut mac = <M as Mac>::new_from_slice(key).unwrap();
+    mac.update(message);
+    mac.finalize().into_bytes().to_vec()
+}
+
+#[test]
+fn matches_reference() {
+    let message = b"The quick brown fox jumps over the lazy dog";
+    // key lengths around the block sizes of 64 and 128 bytes
+    for len in [0, 1, 20, 63, 64, 65, 127, 128, 129, 200] {
+        let key: Vec<u8> = (0..len).map(|i| i as u8).collect();
+        assert_eq!(
+            &*hmac(&mut Software, Algorithm::Sha1, &key, message),
+            reference::<Hmac<Sha1>>(&key, message).as_slice(),
+            "SHA1, key length {}",
+            len
+        );
+        assert_eq!(
+            &*hmac(&mut Software, Algorithm::Sha256, &key, message),
+            reference::<Hmac<Sha256>>(&key, message).as_slice(),
+            "SHA256, key length {}",
+            len
+        );
+        assert_eq!(
+            &*hmac(&mut Software, Algorithm::Sha512, &key, message),
+            reference::<Hmac<Sha512>>(&key, message).as_slice(),
+            "SHA512, key length {}",
+            len
+        );
+    }
+}
+
+#[test]
+fn precomputed_key() {
+    // a key is used for several messages without being prepared again
+    let key: Vec<u8> = (0..100).collect();
+    let hmac_key = HmacKey::new(&mut Software, Algorithm::Sha256, &key);
+    for message in [&b""[..], b"first", b"second"] {
+        assert_eq!(
+            &*hmac_key.sign(&mut Software, message),
+            reference::<Hmac<Sha256>>(&key, message).as_slice()
+        );
+    }
+}
+
+#[test]
+fn default_key() {
+    let message = b"message";
+    assert_eq!(
+        &*HmacKey::default().sign(&mut Software, message),
+        reference::<Hmac<Sha1>>(&[], message).as_slice()
+    );
+}
@@ -0,0 +1,168 @@
+//! Import of Bitwarden, andOTP and 2FAS exports.
+use esp_totp_core::import::{andotp, bitwarden, twofas, Error, Reason, Report};
+use esp_totp_core::otpauth;
+use esp_totp_core::vault::{Cipher, Config};
+use esp_totp_core::Algorithm;
+use serde_json::{json, Value};
+
+fn bytes(export: Value) -> Vec<u8> {
+    serde_json::to_vec(&export).unwrap()
+}
+
+/// The imported tokens have to be accepted by the config.
+fn validate(report: &Report) {
+    let mut config = Config::new(Cipher::Aes128Gcm, None);
+    config.tokens = report.tokens.clone();
+    config.validate().unwrap();
+}
+
+#[test]
+fn bitwarden() {
+    let login =
+        |username: &str, totp: Value| json!({"username": username, "password": "x", "totp": totp});
+    let export = json!({
+        "encrypted": false,
+        "folders": [],
+        "items": [
+            {"type": 1, "name": "GitHub", "login": login("alice", "JBSW Y3DP EHPK 3PXP".into())},
+            {"type": 1, "name": "Example", "login": login("bob", "otpauth://totp/Example:bob@example.com?secret=GEZDGNBV&digits=8".into())},
+            {"type": 1, "name": "Other", "login": login("", "otpauth://totp/carol?secret=GEZDGNBV&period=60".into())},
+            {"type": 1, "name": "Steam", "login": login("dave", "steam://GEZDGNBVGY3TQOJQ".into())},
+            {"type": 1, "name": "No 2FA", "login": login("erin", Value::Null)},
+            {"type": 2, "name": "Secure note", "notes": "..."},
+            {"type": 1, "name": "Broken", "login": login("frank", "otpauth://totp/frank".into())},
+            {"type": 1, "name": "Invalid", "login": login("", "0189".into())},
+            {"type": 1, "name": "Long", "login": login("", "otpauth://totp/x?secret=GEZDGNBV&digits=9".into())},
+        ]
+    });
+    let report = bitwarden::import(&bytes(export)).unwrap();
+
+    let tokens = &report.tokens;
+    assert_eq!(tokens.len(), 4);
+    assert_eq!(tokens[0].name, "alice");
+    assert_eq!(tokens[0].issuer.as_deref(), Some("GitHub"));
+    assert_eq!(tokens[0].key, "JBSWY3DPEHPK3PXP");
+    assert_eq!(tokens[0].kind, "totp");
+    assert_eq!(tokens[1].name, "bob@example.com");
+    assert_eq!(tokens[1].issuer.as_deref(), Some("Example"));
+    assert_eq!(tokens[1].digits, 8);
+    assert_eq!(tokens[2].name, "carol");
+    assert_eq!(tokens[2].issuer.as_deref(), Some("Other"));
+    assert_eq!(tokens[2].period, 60);
+    assert_eq!(tokens[3].kind, "steam");
+    assert_eq!(tokens[3].key, "GEZDGNBVGY3TQOJQ");
+
+    assert_eq!(
+        report.skipped,
+        [
+            (
+                "Broken: frank".into(),
+                Reason::InvalidUri(otpauth::Error::MissingSecret)
+            ),
+            ("Invalid".into(), Reason::InvalidSecret),
+            ("Long".into(), Reason::InvalidParameter("digits")),
+        ]
+    );
+    validate(&report);
+
+    let export = json!({"encrypted": true, "encKeyValidation_DO_NOT_EDIT": "2.x", "data": "2.y"});
+    assert!(matches!(
+        bitwarden::import(&bytes(export)),
+        Err(Error::Encrypted)
+    ));
+}
+
+#[test]
+fn andotp() {
+    let export = json!([
+        {"secret": "JBSWY3DPEHPK3PXP", "issuer": "GitHub", "label": "alice", "digits": 6, "type": "TOTP",
+         "algorithm": "SHA256", "thumbnail": "Github", "last_used": 0, "used_frequency": 0, "period": 30, "tags": []},
+        {"secret": "GEZDGNBV", "issuer": "", "label": "bank", "digits": 8, "type": "HOTP",
+         "algorithm": "SHA1", "counter": 12, "tags": ["work"]},
+        {"secret": "GEZDGNBVGY3TQOJQ", "issuer": "Steam", "label": "dave", "digits": 5, "type": "STEAM",
+         "algorithm": "SHA1", "period": 30},
+        {"secret": "0123456789abcdef", "issuer": "", "label": "vpn", "digits": 6, "type": "MOTP",
+         "algorithm": "MD5", "period": 10},
+        {"secret": "not hex", "issuer": "", "label": "old vpn", "digits": 6, "type": "MOTP", "period": 10},
+        {"secret": "JBSWY3DP", "issuer": "Bank", "label": "eve", "digits": 6, "type": "TOTP", "algorithm": "MD5"},
+        {"secret": "JBSWY3DP", "issuer": "", "label": "frank", "digits": 6, "type": "TOTP", "period": 0},
+    ]);
+    let report = andotp::import(&bytes(export)).unwrap();
+
+    let tokens = &report.tokens;
+    assert_eq!(tokens.len(), 4);
+    assert_eq!(tokens[0].name, "alice");
+    assert_eq!(tokens[0].algorithm, Algorithm::Sha256);
+    assert_eq!(tokens[1].kind, "hotp");
+    assert_eq!(tokens[1].counter, 12);
+    assert_eq!(tokens[1].digits, 8);
+    assert_eq!(tokens[2].kind, "steam");
+    // the 5 characters of Steam are implied by the type
+    assert_eq!(tokens[2].digits, 6);
+    assert_eq!(tokens[3].kind, "motp");
+    // the init secret, the key is its hex decoded bytes
+    assert_eq!(tokens[3].key, "AERUKZ4JVPG66");
+
+    assert_eq!(
+        report.skipped,
+        [
+            ("old vpn".into(), Reason::InvalidSecret),
+            (
+                "Bank: eve".into(),
+                Reason::UnsupportedAlgorithm("MD5".into())
+            ),
+            ("frank".into(), Reason::InvalidParameter("period")),
+        ]
+    );
+    validate(&report);
+
+    assert!(matches!(
+        andotp::import(b"\x00\x00\x27\x10encrypted"),
+        Err(Error::Deserialize(_))
+    ));
+}
+
+#[test]
+fn twofas() {
+    let otp = |account: &str, issuer: &str, kind: &str| {
+        json!({"label": "", "account": account, "issuer": issuer, "digits": 6, "period": 30,
+               "algorithm": "SHA1", "counter": 3, "tokenType": kind, "source": "Manual"})
+    };
+    let mut export = json!({
+        "services": [
+            {"name": "GitHub", "secret": "JBSWY3DPEHPK3PXP", "updatedAt": 0,
+             "otp": otp("alice", "GitHub", "TOTP"), "order": {"position": 0}},
+            {"name": "My Bank", "secret": "GEZDGNBV", "otp": otp("bob", "", "HOTP")},
+            {"name": "Steam", "secret": "GEZDGNBVGY3TQOJQ", "otp": otp("carol", "Steam", "STEAM")},
+            {"name": "Mail", "secret": "GEZDGNBV", "otp": otp("dave", "Mail", "SMS")},
+        ],
+        "updatedAt": 0,
+        "schemaVersion": 4,
+        "appVersionCode": 5000000,
+        "appOrigin": "android",
+        "groups": []
+    });
+    export["services"][2]["otp"]["digits"] = 5.into();
+    let report = twofas::import(&bytes(export)).unwrap();
+
+    let tokens = &report.tokens;
+    assert_eq!(tokens.len(), 3);
+    assert_eq!(tokens[0].name, "alice");
+    assert_eq!(tokens[0].issuer.as_deref(), Some("GitHub"));
+    assert_eq!(tokens[1].name, "bob");
+    assert_eq!(tokens[1].issuer.as_deref(), Some("My Bank"));
+    assert_eq!(tokens[1].kind, "hotp");
+    assert_eq!(tokens[1].counter, 3);
+    assert_eq!(tokens[2].kind, "steam");
+    assert_eq!(
+        report.skipped,
+        [("Mail: dave".into(), Reason::UnsupportedType("sms".into()))]
+    );
+    validate(&report);
+
+    let export = json!({"services": [], "servicesEncrypted": "abc:def:ghi", "schemaVersion": 4});
+    assert!(matches!(
+        twofas::import(&bytes(export)),
+        Err(Error::Encrypted)
+    ));
+}
@@ -0,0 +1,281 @@
+//! Google Authenticator exports (`otpauth-migration://`).
+use data_encoding::{BASE32_NOPAD, BASE64};
+use esp_totp_core::migration::{self, parse, parse_batch, Batch, Error};
+use esp_totp_core::vault::{self, Token, VERSION};
+use esp_totp_core::Algorithm;
+use serde_json::json;
+
+fn varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn bytes(out: &mut Vec<u8>, field: u64, value: &[u8]) {
+    varint(out, field << 3 | 2);
+    varint(out, value.len() as u64);
+    out.extend_from_slice(value);
+}
+
+fn number(out: &mut Vec<u8>, field: u64, value: u64) {
+    varint(out, field << 3);
+    varint(out, value);
+}
+
+/// `OtpParameters` with secret, name, issuer, algorithm, digits, type and counter.
+fn parameters(secret: &[u8], name: &str, issuer: &str, enums: [u64; 3], counter: u64) -> Vec<u8> {
+    let mut out = Vec::new();
+    bytes(&mut out, 1, secret);
+    bytes(&mut out, 2, name.as_bytes());
+    bytes(&mut out, 3, issuer.as_bytes());
+    for (field, value) in (4..).zip(enums) {
+        number(&mut out, field, value);
+    }
+    number(&mut out, 7, counter);
+    out
+}
+
+/// The URI of a `MigrationPayload`, the way the app percent-encodes the base64 data.
+fn uri(tokens: &[Vec<u8>], batch: Batch) -> String {
+    let mut out = Vec::new();
+    for token in tokens {
+        bytes(&mut out, 1, token);
+    }
+    number(&mut out, 2, 1);
+    number(&mut out, 3, batch.size.into());
+    number(&mut out, 4, batch.index.into());
+    number(&mut out, 5, batch.id as u64);
+    let data = BASE64
+        .encode(&out)
+        .replace('+', "%2B")
+        .replace('/', "%2F")
+        .replace('=', "%3D");
+    format!("otpauth-migration://offline?data={}", data)
+}
+
+const SINGLE: Batch = Batch {
+    id: -12345,
+    index: 0,
+    size: 1,
+};
+
+#[test]
+fn payload() {
+    let uri = uri(
+        &[
+            parameters(
+                b"Hello!\xde\xad\xbe\xef",
+                "Example:alice@google.com",
+                "Example",
+                [1, 1, 2],
+                0,
+            ),
+            parameters(b"12345678901234567890", "bob", "", [2, 2, 1], 42),
+            parameters(b"another secret", "", "Steam?", [0, 0, 0], 0),
+        ],
+        SINGLE,
+    );
+    let payload = parse(&uri).unwrap();
+    assert_eq!(payload.batch, SINGLE);
+    let tokens = payload.tokens;
+
+    assert_eq!(tokens[0].name, "alice@google.com");
+    assert_eq!(tokens[0].issuer.as_deref(), Some("Example"));
+    assert_eq!(tokens[0].key, "JBSWY3DPEHPK3PXP");
+    assert_eq!(tokens[0].kind, "totp");
+    assert_eq!(tokens[0].digits, 6);
+
+    assert_eq!(tokens[1].name, "bob");
+    assert_eq!(tokens[1].issuer, None);
+    assert_eq!(tokens[1].key, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+    assert_eq!(tokens[1].algorithm, Algorithm::Sha256);
+    assert_eq!(tokens[1].digits, 8);
+    assert_eq!(tokens[1].kind, "hotp");
+    assert_eq!(tokens[1].counter, 42);
+
+    // unset enums
+    assert_eq!(tokens[2].name, "Steam?");
+    assert_eq!(tokens[2].algorithm, Algorithm::Sha1);
+    assert_eq!(tokens[2].kind, "totp");
+}
+
+#[test]
+fn example() {
+    // as exported by the app, without batch fields
+    let payload = parse(
+        "otpauth-migration://offline?data=CjEKCkhlbGxvId6tvu8SGEV4YW1wbGU6YWxpY2VAZ29vZ2xlLmNvbRoHRXhhbXBsZTAC",
+    )
+    .unwrap();
+    assert_eq!(payload.batch.size, 1);
+    assert_eq!(payload.tokens.len(), 1);
+    assert_eq!(payload.tokens[0].name, "alice@google.com");
+    assert_eq!(payload.tokens[0].issuer.as_deref(), Some("Example"));
+    assert_eq!(payload.tokens[0].key, "JBSWY3DPEHPK3PXP");
+    assert_eq!(payload.tokens[0].kind, "totp");
+}
+
+#[test]
+fn batches() {
+    let part = |index, name: &str| {
+        let batch = Batch {
+            id: 7,
+            index,
+            size: 3,
+        };
+        uri(&[parameters(b"secret", name, "", [1, 1, 2], 0)], batch)
+    };
+    let (first, second, third) = (part(0, "first"), part(1, "second"), part(2, "third"));
+
+    let tokens = parse_batch([&third, &first, &second].map(String::as_str)).unwrap();
+    let names: Vec<&str> = tokens.iter().map(|token| token.name.as_str()).collect();
+    assert_eq!(names, ["first", "second", "third"]);
+
+    for parts in [vec![&first, &third], vec![&first, &second, &second, &third]] {
+        assert_eq!(
+            parse_batch(parts.into_iter().map(String::as_str)).unwrap_err(),
+            Error::IncompleteBatch(7)
+        );
+    }
+}
+
+#[test]
+fn invalid() {
+    let token = |enums| uri(&[parameters(b"secret", "alice", "", enums, 0)], SINGLE);
+    for (uri, error) in [
+        (
+            "otpauth://totp/alice?secret=JBSWY3DP".into(),
+            Error::InvalidScheme,
+        ),
+        ("otpauth-migration://offline?".into(), Error::MissingData),
+        (
+            "otpauth-migration://offline?data=%%%".into(),
+            Error::InvalidEncoding,
+        ),
+        (
+            "otpauth-migration://offline?data=CgA%3D".into(),
+            Error::MissingSecret,
+        ),
+        (
+            "otpauth-migration://offline?data=Cg%3D%3D".into(),
+            Error::InvalidPayload,
+        ),
+        (token([4, 1, 2]), Error::UnsupportedAlgorithm),
+        (token([1, 3, 2]), Error::UnsupportedDigits),
+        (token([1, 1, 3]), Error::UnsupportedType),
+    ] {
+        assert_eq!(parse(&uri).unwrap_err(), error, "{}", uri);
+    }
+}
+
+#[test]
+fn config_entry() {
+    let batch = |index| Batch {
+        id: 1,
+        index,
+        size: 2,
+    };
+    let first = uri(&[parameters(b"first", "A:a", "A", [1, 1, 2], 0)], batch(0));
+    let second = uri(
+        &[
+            parameters(b"second", "b", "", [1, 1, 1], 3),
+            parameters(b"third", "c", "", [1, 2, 2], 0),
+        ],
+        batch(1),
+    );
+
+    let mut raw = json!({
+        "version": VERSION,
+        "cipher": "aes-128-gcm",
+        "tokens": [first, {"name": "GitHub", "key": "JBSWY3DP"}, second],
+    });
+    let (config, _) = vault::load(&mut raw).unwrap();
+    let names: Vec<&str> = config
+        .tokens
+        .iter()
+        .map(|token| token.name.as_str())
+        .collect();
+    assert_eq!(names, ["a", "GitHub", "b", "c"]);
+    assert_eq!(config.tokens[2].counter, 3);
+    // the payload holds the plain secrets
+    assert!(config
+        .tokens
+        .iter()
+        .all(|token| token.plaintext != (token.name == "GitHub")));
+    assert_eq!(config.tokens[3].digits, 8);
+    assert_eq!(raw["tokens"][2]["counter"], 3);
+
+    let mut raw = json!({
+        "version": VERSION,
+        "cipher": "aes-128-gcm",
+        "tokens": [{"name": "GitHub", "key": "JBSWY3DP"}, second],
+    });
+    assert!(matches!(
+        vault::load(&mut raw),
+        Err(vault::Error::InvalidMigration(1, Error::IncompleteBatch(1)))
+    ));
+}
+
+#[test]
+fn export() {
+    let tokens: Vec<Token> = (0..12)
+        .map(|i| {
+            let mut token = Token::new(format!("token {}", i), vec![i; 10]);
+            token.issuer = (i % 2 == 0).then(|| "Even".into());
+            if i == 11 {
+                token.kind = "hotp".into();
+                token.counter = 99;
+                token.digits = 8;
+                token.algorithm = Algorithm::Sha256;
+            }
+            token
+        })
+        .collect();
+    let refs: Vec<&Token> = tokens.iter().collect();
+    let uris = migration::export(&refs, -3);
+    assert_eq!(uris.len(), 2);
+
+    let first = parse(&uris[0]).unwrap();
+    assert_eq!(first.tokens.len(), 10);
+    assert_eq!(
+        first.batch,
+        Batch {
+            id: -3,
+            index: 0,
+            size: 2
+        }
+    );
+
+    let parsed = parse_batch(uris.iter().map(String::as_str)).unwrap();
+    assert_eq!(parsed.len(), 12);
+    for (parsed, token) in parsed.iter().zip(tokens.iter()) {
+        assert_eq!(parsed.name, token.name);
+        assert_eq!(parsed.issuer, token.issuer);
+        assert_eq!(parsed.key, BASE32_NOPAD.encode(&token.secret));
+        assert_eq!(parsed.kind, token.kind);
+        assert_eq!(parsed.counter, token.counter);
+        assert_eq!(parsed.digits, token.digits);
+        assert_eq!(parsed.algorithm, token.algorithm);
+    }
+}
+
+#[test]
+fn unsupported_export() {
+    let mut token = Token::new("token".into(), b"secret".to_vec());
+    migration::check(&token).unwrap();
+    token.period = 60;
+    assert_eq!(migration::check(&token), Err(Error::UnsupportedPeriod));
+    token.period = 30;
+    token.offset_seconds = 20;
+    assert_eq!(migration::check(&token), Err(Error::UnsupportedTimeShift));
+    token.offset_seconds = 0;
+    token.t0 = 30;
+    assert_eq!(migration::check(&token), Err(Error::UnsupportedTimeShift));
+    token.t0 = 0;
+    token.digits = 7;
+    assert_eq!(migration::check(&token), Err(Error::UnsupportedDigits));
+    token.digits = 6;
+    token.kind = "steam".into();
+    assert_eq!(migration::check(&token), Err(Error::UnsupportedType));
+}
@@ -0,0 +1,28 @@
+//! Mobile-OTP codes, the expected values were computed with `md5sum` following the mOTP spec.
+use esp_totp_core::otp::motp;
+
+const SECRET: [u8; 8] = [0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef];
+const PIN: &str = "1234";
+
+#[test]
+fn codes() {
+    assert_eq!(motp(&SECRET, PIN, 59).to_string(), "3982c0");
+    assert_eq!(motp(&SECRET, PIN, 1700000000).to_string(), "05aae5");
+    assert_eq!(motp(&SECRET, PIN, 1700000010).to_string(), "c1b651");
+}
+
+#[test]
+fn window() {
+    let first = motp(&SECRET, PIN, 1700000000);
+    let last = motp(&SECRET, PIN, 1700000009);
+    assert_eq!(first.code, last.code);
+    assert_eq!(first.valid_until, 1700000010);
+}
+
+#[test]
+fn leading_zeros() {
+    // the code must always have 6 digits, even if the hash starts with zeros
+    for timestamp in (0..100_000).step_by(10) {
+        assert_eq!(motp(&SECRET, PIN, timestamp).to_string().len(), 6);
+    }
+}
@@ -0,0 +1,179 @@
+//! Tokens given as otpauth URIs.
+use esp_totp_core::otpauth::{parse, to_uri, Error};
+use esp_totp_core::vault::{self, Token, VERSION};
+use esp_totp_core::Algorithm;
+use serde_json::json;
+
+#[test]
+fn totp() {
+    let token =
+        parse("otpauth://totp/Example:alice@google.com?secret=JBSWY3DPEHPK3PXP&issuer=Example")
+            .unwrap();
+    assert_eq!(token.name, "alice@google.com");
+    assert_eq!(token.issuer.as_deref(), Some("Example"));
+    assert_eq!(token.key, "JBSWY3DPEHPK3PXP");
+    assert_eq!(token.kind, "totp");
+    assert_eq!(token.algorithm, Algorithm::Sha1);
+    assert_eq!(token.digits, 6);
+    assert_eq!(token.period, 30);
+}
+
+#[test]
+fn parameters() {
+    let token = parse(
+        "otpauth://TOTP/ACME%20Co:john.doe%40email.com?secret=jbsw%20y3dp%3D%3D&algorithm=sha256\
+         &digits=8&period=60&image=https%3A%2F%2Fexample.com%2Flogo.png",
+    )
+    .unwrap();
+    assert_eq!(token.name, "john.doe@email.com");
+    assert_eq!(token.issuer.as_deref(), Some("ACME Co"));
+    assert_eq!(token.key, "JBSWY3DP");
+    assert_eq!(token.algorithm, Algorithm::Sha256);
+    assert_eq!(token.digits, 8);
+    assert_eq!(token.period, 60);
+
+    let token = parse("otpauth://hotp/alice?secret=JBSWY3DP&counter=42&issuer=Big%20Bank").unwrap();
+    assert_eq!(token.kind, "hotp");
+    assert_eq!(token.counter, 42);
+    assert_eq!(token.name, "alice");
+    assert_eq!(token.issuer.as_deref(), Some("Big Bank"));
+
+    let token = parse("otpauth://totp/ACME%3Ajohn?secret=JBSWY3DP").unwrap();
+    assert_eq!(token.name, "john");
+    assert_eq!(token.issuer.as_deref(), Some("ACME"));
+
+    let token = parse("otpauth://steam/Steam:?secret=JBSWY3DP").unwrap();
+    assert_eq!(token.kind, "steam");
+    assert_eq!(token.name, "Steam");
+}
+
+#[test]
+fn invalid() {
+    for (uri, error) in [
+        ("https://example.com/?secret=JBSWY3DP", Error::InvalidScheme),
+        ("otpauth://totp?secret=JBSWY3DP", Error::MissingLabel),
+        ("otpauth://totp/?secret=JBSWY3DP", Error::MissingLabel),
+        ("otpauth://totp/alice", Error::MissingSecret),
+        (
+            "otpauth://sms/alice?secret=JBSWY3DP",
+            Error::UnknownType("sms".into()),
+        ),
+        (
+            "otpauth://totp/alice%2?secret=JBSWY3DP",
+            Error::InvalidEncoding,
+        ),
+        (
+            "otpauth://totp/alice%FF?secret=JBSWY3DP",
+            Error::InvalidEncoding,
+        ),
+        (
+            "otpauth://totp/alice?secret=JBSWY3D1",
+            Error::InvalidParameter("secret"),
+        ),
+        (
+            "otpauth://totp/alice?secret=JBSWY3DP&algorithm=MD5",
+            Error::InvalidParameter("algorithm"),
+        ),
+        (
+            "otpauth://totp/alice?secret=JBSWY3DP&digits=six",
+            Error::InvalidParameter("digits"),
+        ),
+        (
+            "otpauth://totp/alice?secret=JBSWY3DP&period=-30",
+            Error::InvalidParameter("period"),
+        ),
+    ] {
+        assert_eq!(parse(uri).unwrap_err(), error, "{}", uri);
+    }
+}
+
+#[test]
+fn config_entry() {
+    let mut raw = json!({
+        "version": VERSION,
+        "cipher": "aes-128-gcm",
+        "tokens": [
+            {"name": "GitHub", "key": "JBSWY3DP"},
+            "otpauth://hotp/Bank:alice?secret=GEZDGNBV&counter=7",
+        ],
+    });
+    let (config, migrated) = vault::load(&mut raw).unwrap();
+    assert!(!migrated);
+    assert_eq!(config.tokens[1].name, "alice");
+    assert_eq!(config.tokens[1].issuer.as_deref(), Some("Bank"));
+    assert_eq!(config.tokens[1].key, "GEZDGNBV");
+    assert_eq!(config.tokens[1].counter, 7);
+    assert!(config.tokens[1].plaintext && !config.tokens[0].plaintext);
+    // expanded, so the counter can be stored
+    assert_eq!(raw["tokens"][1]["counter"], 7);
+
+    let mut raw = json!({"version": VERSION, "cipher": "aes-128-gcm", "tokens": [
+        {"name": "GitHub", "key": "JBSWY3DP"},
+        "otpauth://totp/alice?secret=GEZDGNBV&digits=9",
+    ]});
+    assert!(matches!(
+        vault::load(&mut raw),
+        Err(vault::Error::InvalidDigits(name)) if name == "alice"
+    ));
+    let mut raw = json!({"version": VERSION, "cipher": "aes-128-gcm", "tokens": [
+        {"name": "GitHub", "key": "JBSWY3DP"},
+        "otpauth://totp/alice",
+    ]});
+    assert!(matches!(
+        vault::load(&mut raw),
+        Err(vault::Error::InvalidUri(1, Error::MissingSecret))
+    ));
+}
+
+#[test]
+fn round_trip() {
+    let mut totp = Token::new("alice@example.com".into(), b"12345678901234567890".to_vec());
+    totp.issuer = Some("ACME Co: Mail & more".into());
+    totp.algorithm = Algorithm::Sha512;
+    totp.digits = 8;
+    totp.period = 60;
+    let uri = to_uri(&totp).unwrap();
+    assert_eq!(
+        uri,
+        "otpauth://totp/ACME%20Co%3A%20Mail%20%26%20more:alice%40example.com\
+         ?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=ACME%20Co%3A%20Mail%20%26%20more\
+         &algorithm=SHA512&digits=8&period=60"
+    );
+
+    let mut hotp = Token::new("bank".into(), b"secret".to_vec());
+    hotp.kind = "hotp".into();
+    hotp.counter = 42;
+    let mut ocra = Token::new("ocra".into(), b"secret".to_vec());
+    ocra.kind = "ocra".into();
+    ocra.suite = Some("OCRA-1:HOTP-SHA1-6:QN08".into());
+
+    for token in [totp, hotp, ocra] {
+        let parsed = parse(&to_uri(&token).unwrap()).unwrap();
+        assert_eq!(parsed.name, token.name);
+        assert_eq!(parsed.issuer, token.issuer);
+        assert_eq!(
+            parsed.key,
+            data_encoding::BASE32_NOPAD.encode(&token.secret)
+        );
+        assert_eq!(parsed.kind, token.kind);
+        assert_eq!(parsed.algorithm, token.algorithm);
+        assert_eq!(parsed.digits, token.digits);
+        assert_eq!(parsed.period, token.period);
+        assert_eq!(parsed.counter, token.counter);
+        assert_eq!(parsed.suite, token.suite);
+    }
+}
+
+#[test]
+fn time_shifted_export() {
+    let mut totp = Token::new("alice".into(), b"secret".to_vec());
+    totp.offset_seconds = -20;
+    assert_eq!(to_uri(&totp), Err(Error::UnsupportedTimeShift));
+    totp.offset_seconds = 0;
+    totp.t0 = 60;
+    assert_eq!(to_uri(&totp), Err(Error::UnsupportedTimeShift));
+
+    // the codes of a HOTP token do not depend on the time
+    totp.kind = "hotp".into();
+    to_uri(&totp).unwrap();
+}
@@ -0,0 +1,70 @@
+//! Test vectors of RFC 6070 (PBKDF2-HMAC-SHA1) and the commonly used PBKDF2-HMAC-SHA256 ones.
+use data_encoding::HEXLOWER;
+use esp_totp_core::kdf::pbkdf2;
+use esp_totp_core::software::Software;
+use esp_totp_core::Algorithm;
+
+fn derive(
+    algorithm: Algorithm,
+    password: &[u8],
+    salt: &[u8],
+    iterations: u32,
+    len: usize,
+) -> String {
+    let mut output = vec![0u8; len];
+    pbkdf2(
+        &mut Software,
+        algorithm,
+        password,
+        salt,
+        iterations,
+        &mut output,
+    );
+    HEXLOWER.encode(&output)
+}
+
+#[test]
+fn rfc6070() {
+    for (iterations, expected) in [
+        (1, "0c60c80f961f0e71f3a9b524af6012062fe037a6"),
+        (2, "ea6c014dc72d6f8ccd1ed92ace1d41f0d8de8957"),
+        (4096, "4b007901b765489abead49d926f721d065a429c1"),
+    ] {
+        assert_eq!(
+            derive(Algorithm::Sha1, b"password", b"salt", iterations, 20),
+            expected
+        );
+    }
+    // several blocks
+    assert_eq!(
+        derive(
+            Algorithm::Sha1,
+            b"passwordPASSWORDpassword",
+            b"saltSALTsaltSALTsaltSALTsaltSALTsalt",
+            4096,
+            25
+        ),
+        "3d2eec4fe41c849b80c8d83662c0e44a8b291a964cf2f07038"
+    );
+    assert_eq!(
+        derive(Algorithm::Sha1, b"pass\0word", b"sa\0lt", 4096, 16),
+        "56fa6aa75548099dcc37d7f03425e0c3"
+    );
+}
+
+#[test]
+fn sha256() {
+    assert_eq!(
+        derive(Algorithm::Sha256, b"password", b"salt", 1, 32),
+        "120fb6cffcf8b32c43e7225256c4f837a86548c92ccc35480805987cb70be17b"
+    );
+    assert_eq!(
+        derive(Algorithm::Sha256, b"password", b"salt", 4096, 32),
+        "c5e478d59288c841aa530db6845c4c8d962893a001ce4e11a4963873aa98134a"
+    );
+    // shorter keys are a prefix of the longer ones
+    assert_eq!(
+        derive(Algorithm::Sha256, b"password", b"salt", 4096, 16),
+        "c5e478d59288c841aa530db6845c4c8d"
+    );
+}
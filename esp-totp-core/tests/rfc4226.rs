@@ -0,0 +1,34 @@
+//! Test vectors of RFC 4226, Appendix D.
+use esp_totp_core::otp::{hotp, hotp_value};
+use esp_totp_core::software::Software;
+use esp_totp_core::{Algorithm, HmacKey};
+
+const SECRET: &[u8] = b"12345678901234567890";
+
+const TRUNCATED: [u32; 10] = [
+    1284755224, 1094287082, 137359152, 1726969429, 1640338314, 868254676, 1918287922, 82162583,
+    673399871, 645520489,
+];
+
+const CODES: [&str; 10] = [
+    "755224", "287082", "359152", "969429", "338314", "254676", "287922", "162583", "399871",
+    "520489",
+];
+
+#[test]
+fn truncated_values() {
+    let key = HmacKey::new(&mut Software, Algorithm::Sha1, SECRET);
+    for (counter, expected) in TRUNCATED.iter().enumerate() {
+        let value = hotp_value(&mut Software, &key, counter as u64);
+        assert_eq!(value, *expected, "counter {}", counter);
+    }
+}
+
+#[test]
+fn codes() {
+    let key = HmacKey::new(&mut Software, Algorithm::Sha1, SECRET);
+    for (counter, expected) in CODES.iter().enumerate() {
+        let token = hotp(&mut Software, &key, 6, counter as u64);
+        assert_eq!(token.to_string(), *expected, "counter {}", counter);
+    }
+}
@@ -0,0 +1,42 @@
+//! Test vectors of RFC 6238, Appendix B.
+use esp_totp_core::otp::totp;
+use esp_totp_core::software::Software;
+use esp_totp_core::{Algorithm, HmacKey};
+
+const SHA1_SECRET: &[u8] = b"12345678901234567890";
+const SHA256_SECRET: &[u8] = b"12345678901234567890123456789012";
+const SHA512_SECRET: &[u8] =
+    b"1234567890123456789012345678901234567890123456789012345678901234";
+
+const VECTORS: [(u64, &str, &str, &str); 6] = [
+    (59, "94287082", "46119246", "90693936"),
+    (1111111109, "07081804", "68084774", "25091201"),
+    (1111111111, "14050471", "67062674", "99943326"),
+    (1234567890, "89005924", "91819424", "93441116"),
+    (2000000000, "69279037", "90698825", "38618901"),
+    (20000000000, "65353130", "77737706", "47863826"),
+];
+
+fn check(algorithm: Algorithm, secret: &[u8], expected: impl Fn(usize) -> &'static str) {
+    let key = HmacKey::new(&mut Software, algorithm, secret);
+    for (i, (timestamp, ..)) in VECTORS.iter().enumerate() {
+        let token = totp(&mut Software, &key, 8, 30, *timestamp);
+        assert_eq!(token.to_string(), expected(i), "timestamp {}", timestamp);
+        assert_eq!(token.valid_until, (timestamp / 30 + 1) * 30);
+    }
+}
+
+#[test]
+fn sha1() {
+    check(Algorithm::Sha1, SHA1_SECRET, |i| VECTORS[i].1);
+}
+
+#[test]
+fn sha256() {
+    check(Algorithm::Sha256, SHA256_SECRET, |i| VECTORS[i].2);
+}
+
+#[test]
+fn sha512() {
+    check(Algorithm::Sha512, SHA512_SECRET, |i| VECTORS[i].3);
+}
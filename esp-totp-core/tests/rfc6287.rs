@@ -0,0 +1,175 @@
+//! Test vectors of RFC 6287, Appendix C.
+use esp_totp_core::ocra::{ocra, Challenge, Error, Input, Suite};
+use esp_totp_core::software::Software;
+use esp_totp_core::{Algorithm, HmacKey};
+
+const SEED: &[u8] = b"12345678901234567890";
+const SEED32: &[u8] = b"12345678901234567890123456789012";
+const SEED64: &[u8] = b"1234567890123456789012345678901234567890123456789012345678901234";
+const PIN: &str = "1234";
+
+fn response(suite: &str, key: &[u8], input: Input) -> String {
+    let suite = Suite::parse(suite).unwrap();
+    let key = HmacKey::new(&mut Software, suite.algorithm, key);
+    ocra(&mut Software, &suite, &key, &input)
+        .unwrap()
+        .to_string()
+}
+
+fn question(i: usize) -> String {
+    i.to_string().repeat(8)
+}
+
+#[test]
+fn one_way_sha1() {
+    let expected = [
+        "237653", "243178", "653583", "740991", "608993", "388898", "816933", "224598", "750600",
+        "294470",
+    ];
+    for (i, expected) in expected.iter().enumerate() {
+        let challenge = question(i);
+        let input = Input {
+            challenge: &challenge,
+            ..Default::default()
+        };
+        assert_eq!(response("OCRA-1:HOTP-SHA1-6:QN08", SEED, input), *expected);
+    }
+}
+
+#[test]
+fn one_way_sha256_counter_pin() {
+    let expected = [
+        "65347737", "86775851", "78192410", "71565254", "10104329", "65983500", "70069104",
+        "91771096", "75011558", "08522129",
+    ];
+    for (counter, expected) in expected.iter().enumerate() {
+        let input = Input {
+            counter: counter as u64,
+            challenge: "12345678",
+            pin: PIN,
+            ..Default::default()
+        };
+        let suite = "OCRA-1:HOTP-SHA256-8:C-QN08-PSHA1";
+        assert_eq!(response(suite, SEED32, input), *expected);
+    }
+}
+
+#[test]
+fn one_way_sha256_pin() {
+    let expected = ["83238735", "01501458", "17957585", "86776967", "86807031"];
+    for (i, expected) in expected.iter().enumerate() {
+        let challenge = question(i);
+        let input = Input {
+            challenge: &challenge,
+            pin: PIN,
+            ..Default::default()
+        };
+        let suite = "OCRA-1:HOTP-SHA256-8:QN08-PSHA1";
+        assert_eq!(response(suite, SEED32, input), *expected);
+    }
+}
+
+#[test]
+fn one_way_sha512_counter() {
+    let expected = [
+        "07016083", "63947962", "70123924", "25341727", "33203315", "34205738", "44343969",
+        "51946085", "20403879", "31409299",
+    ];
+    for (i, expected) in expected.iter().enumerate() {
+        let challenge = question(i);
+        let input = Input {
+            counter: i as u64,
+            challenge: &challenge,
+            ..Default::default()
+        };
+        assert_eq!(
+            response("OCRA-1:HOTP-SHA512-8:C-QN08", SEED64, input),
+            *expected
+        );
+    }
+}
+
+#[test]
+fn one_way_sha512_time() {
+    let expected = ["95209754", "55907591", "22048402", "24218844", "36209546"];
+    for (i, expected) in expected.iter().enumerate() {
+        let challenge = question(i);
+        let input = Input {
+            challenge: &challenge,
+            timestamp: 0x132d0b6 * 60 + 59,
+            ..Default::default()
+        };
+        assert_eq!(
+            response("OCRA-1:HOTP-SHA512-8:QN08-T1M", SEED64, input),
+            *expected
+        );
+    }
+}
+
+#[test]
+fn time_window() {
+    let suite = Suite::parse("OCRA-1:HOTP-SHA512-8:QN08-T1M").unwrap();
+    let input = Input {
+        challenge: "00000000",
+        timestamp: 0x132d0b6 * 60,
+        ..Default::default()
+    };
+    let key = HmacKey::new(&mut Software, suite.algorithm, SEED64);
+    let token = ocra(&mut Software, &suite, &key, &input).unwrap();
+    assert_eq!(token.valid_until, 0x132d0b7 * 60);
+}
+
+#[test]
+fn parse() {
+    let suite = Suite::parse("OCRA-1:HOTP-SHA256-8:C-QH40-PSHA512-S064-T1H").unwrap();
+    assert_eq!(
+        suite.as_str(),
+        "OCRA-1:HOTP-SHA256-8:C-QH40-PSHA512-S064-T1H"
+    );
+    assert_eq!(suite.algorithm, Algorithm::Sha256);
+    assert_eq!(suite.digits, 8);
+    assert!(suite.counter);
+    assert_eq!(suite.challenge, Challenge::Hex);
+    assert_eq!(suite.challenge_length, 40);
+    assert_eq!(suite.pin, Some(Algorithm::Sha512));
+    assert_eq!(suite.session, Some(64));
+    assert_eq!(suite.time_step, Some(3600));
+
+    for invalid in [
+        "",
+        "OCRA-2:HOTP-SHA1-6:QN08",
+        "OCRA-1:HOTP-MD5-6:QN08",
+        "OCRA-1:HOTP-SHA1-0:QN08",
+        "OCRA-1:HOTP-SHA1-6:C",
+        "OCRA-1:HOTP-SHA1-6:QX08",
+        "OCRA-1:HOTP-SHA1-6:QN08-T60S",
+        "OCRA-1:HOTP-SHA1-6:QN08-T0H",
+        "OCRA-1:HOTP-SHA1-6:QN08-T1M-PSHA1",
+        "OCRA-1:HOTP-SHA1-6:QN08:",
+        "OCRA-1:HOTP-SHA1-6:QN08-",
+        "OCRA-1:HOTP-SHA1-6:QN08-Tä",
+    ] {
+        assert_eq!(
+            Suite::parse(invalid),
+            Err(Error::InvalidSuite),
+            "{}",
+            invalid
+        );
+    }
+}
+
+#[test]
+fn invalid_challenge() {
+    let suite = Suite::parse("OCRA-1:HOTP-SHA1-6:QN08").unwrap();
+    let key = HmacKey::new(&mut Software, suite.algorithm, SEED);
+    for challenge in ["", "123456789", "1234abcd"] {
+        let input = Input {
+            challenge,
+            ..Default::default()
+        };
+        assert_eq!(
+            ocra(&mut Software, &suite, &key, &input).err(),
+            Some(Error::InvalidChallenge)
+        );
+    }
+}
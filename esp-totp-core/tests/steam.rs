@@ -0,0 +1,21 @@
+//! Steam Guard codes, the expected values are taken from the ValvePython steam library.
+use esp_totp_core::otp::steam;
+use esp_totp_core::software::Software;
+use esp_totp_core::{Algorithm, HmacKey};
+
+const SECRET: &[u8] = b"superdupersecret";
+
+#[test]
+fn codes() {
+    let key = HmacKey::new(&mut Software, Algorithm::Sha1, SECRET);
+    assert_eq!(steam(&mut Software, &key, 3000029).to_string(), "94R9D");
+    assert_eq!(steam(&mut Software, &key, 3000030).to_string(), "YRGQJ");
+}
+
+#[test]
+fn window() {
+    let key = HmacKey::new(&mut Software, Algorithm::Sha1, SECRET);
+    let token = steam(&mut Software, &key, 3000030);
+    assert_eq!(token.valid_until, 3000060);
+    assert_eq!(token.digits, 5);
+}
@@ -0,0 +1,74 @@
+//! Tokens with a custom epoch and clock offset, the codes are the ones of RFC 6238 and Steam
+//! shifted in time.
+use esp_totp_core::otp::{steam, totp, TimeShift};
+use esp_totp_core::software::Software;
+use esp_totp_core::{Algorithm, HmacKey};
+
+const SECRET: &[u8] = b"12345678901234567890";
+
+fn sha1(shift: TimeShift, timestamp: u64) -> (String, u64) {
+    let key = HmacKey::new(&mut Software, Algorithm::Sha1, SECRET);
+    let token = shift.token(timestamp, |t| totp(&mut Software, &key, 8, 30, t));
+    (token.to_string(), token.valid_until)
+}
+
+#[test]
+fn no_shift() {
+    assert_eq!(sha1(TimeShift::default(), 59), ("94287082".into(), 60));
+}
+
+#[test]
+fn t0() {
+    let shift = TimeShift { t0: 100, offset: 0 };
+    assert_eq!(sha1(shift, 159), ("94287082".into(), 160));
+    // the windows start at t0, not at a multiple of the period
+    let shift = TimeShift { t0: 10, offset: 0 };
+    assert_eq!(sha1(shift, 1111111119), ("07081804".into(), 1111111120));
+    assert_eq!(sha1(shift, 1111111121), ("14050471".into(), 1111111150));
+}
+
+#[test]
+fn offset() {
+    // a server running 30 seconds late
+    let shift = TimeShift { t0: 0, offset: -30 };
+    assert_eq!(sha1(shift, 89), ("94287082".into(), 90));
+    // a server running 20 seconds early
+    let shift = TimeShift { t0: 0, offset: 20 };
+    assert_eq!(sha1(shift, 1111111089), ("07081804".into(), 1111111090));
+    assert_eq!(sha1(shift, 1111111091), ("14050471".into(), 1111111120));
+}
+
+#[test]
+fn t0_and_offset() {
+    let shift = TimeShift {
+        t0: 100,
+        offset: -30,
+    };
+    assert_eq!(shift.apply(189), 59);
+    assert_eq!(shift.revert(60), 190);
+    assert_eq!(sha1(shift, 189), ("94287082".into(), 190));
+}
+
+#[test]
+fn without_expiry() {
+    let shift = TimeShift {
+        t0: 100,
+        offset: -30,
+    };
+    assert_eq!(shift.revert(u64::MAX), u64::MAX);
+}
+
+#[test]
+fn before_t0() {
+    let shift = TimeShift { t0: 100, offset: 0 };
+    assert_eq!(shift.apply(50), 0);
+}
+
+#[test]
+fn steam_offset() {
+    let shift = TimeShift { t0: 0, offset: 60 };
+    let key = HmacKey::new(&mut Software, Algorithm::Sha1, b"superdupersecret");
+    let token = shift.token(2999970, |t| steam(&mut Software, &key, t));
+    assert_eq!(token.to_string(), "YRGQJ");
+    assert_eq!(token.valid_until, 3000000);
+}
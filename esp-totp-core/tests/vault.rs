@@ -0,0 +1,275 @@
+//! Encryption and migration of the config on the SD card.
+use aes::cipher::{KeyIvInit, StreamCipher};
+use data_encoding::BASE32_NOPAD;
+use esp_totp_core::software::Software;
+use esp_totp_core::vault::{load, migrate, Cipher, Config, Error, Kdf, Token, VERSION};
+use esp_totp_core::{algorithm, ocra};
+use serde_json::json;
+
+const PIN: &str = "123456";
+const SECRET: &[u8] = b"12345678901234567890";
+
+/// Counts up, good enough as nonces of a test.
+fn counter() -> impl FnMut(&mut [u8]) {
+    let mut next = 0u8;
+    move |buffer: &mut [u8]| {
+        for b in buffer.iter_mut() {
+            *b = next;
+            next = next.wrapping_add(1);
+        }
+    }
+}
+
+/// Encrypts a config, writes it as JSON and loads it again.
+fn store(cipher: Cipher, kdf: Option<Kdf>) -> Config {
+    let mut config = Config::new(cipher, kdf);
+    config
+        .tokens
+        .push(Token::new("first".into(), SECRET.to_vec()));
+    config
+        .tokens
+        .push(Token::new("second".into(), b"another secret".to_vec()));
+    config.encrypt(&mut Software, PIN, &mut counter()).unwrap();
+
+    let mut raw = serde_json::to_value(&config).unwrap();
+    assert!(!migrate(&mut raw).unwrap());
+    Config::from_value(raw).unwrap()
+}
+
+#[test]
+fn round_trip() {
+    let kdf = || Some(Kdf::new(10, &mut counter()));
+    for (cipher, kdf) in [
+        (Cipher::Aes128Ctr, None),
+        (Cipher::Aes128Ctr, kdf()),
+        (Cipher::Aes128Gcm, None),
+        (Cipher::Aes128Gcm, kdf()),
+    ] {
+        let mut config = store(cipher, kdf);
+        assert!(config.tokens.iter().all(|token| token.secret.is_empty()));
+        config.decrypt(&mut Software, PIN).unwrap();
+        assert_eq!(config.tokens[0].secret, SECRET, "{:?}", cipher);
+        assert_eq!(config.tokens[1].secret, b"another secret", "{:?}", cipher);
+    }
+}
+
+#[test]
+fn wrong_pin() {
+    let mut config = store(Cipher::Aes128Gcm, None);
+    assert!(matches!(
+        config.decrypt(&mut Software, "654321"),
+        Err(Error::WrongPin)
+    ));
+    assert!(config.tokens[0].secret.is_empty());
+
+    // not detected without authentication
+    let mut config = store(Cipher::Aes128Ctr, Some(Kdf::new(10, &mut counter())));
+    config.decrypt(&mut Software, "654321").unwrap();
+    assert_ne!(config.tokens[0].secret, SECRET);
+}
+
+#[test]
+fn tampered_key() {
+    let mut config = store(Cipher::Aes128Gcm, None);
+    let mut raw = BASE32_NOPAD
+        .decode(config.tokens[1].key.as_bytes())
+        .unwrap();
+    raw[12] ^= 1;
+    config.tokens[1].key = BASE32_NOPAD.encode(&raw);
+    assert!(matches!(
+        config.decrypt(&mut Software, PIN),
+        Err(Error::InvalidKey(name)) if name == "second"
+    ));
+}
+
+#[test]
+fn unversioned_config() {
+    // the format before the version was introduced, the PIN is the key
+    let iv = [7u8; 16];
+    let mut key = [0u8; 16];
+    key[..PIN.len()].copy_from_slice(PIN.as_bytes());
+    let mut encrypted = SECRET.to_vec();
+    ctr::Ctr64LE::<aes::Aes128>::new(&key.into(), &iv.into()).apply_keystream(&mut encrypted);
+    let mut raw = json!({
+        "nonce": BASE32_NOPAD.encode(&iv),
+        "tokens": [{"name": "GitHub", "key": BASE32_NOPAD.encode(&encrypted)}],
+    });
+
+    assert!(migrate(&mut raw).unwrap());
+    assert_eq!(raw["version"], VERSION);
+    assert_eq!(raw["cipher"], "aes-128-ctr");
+    let mut config = Config::from_value(raw).unwrap();
+    config.decrypt(&mut Software, PIN).unwrap();
+    assert_eq!(config.tokens[0].secret, SECRET);
+    assert_eq!(config.tokens[0].kind, "totp");
+    assert_eq!(config.tokens[0].period, 30);
+}
+
+#[test]
+fn newer_version() {
+    let mut raw = json!({"version": VERSION + 1, "cipher": "aes-256-gcm", "tokens": []});
+    assert!(matches!(
+        migrate(&mut raw),
+        Err(Error::UnsupportedVersion(version)) if version == VERSION + 1
+    ));
+    let mut raw = json!({"version": "1", "tokens": []});
+    assert!(matches!(migrate(&mut raw), Err(Error::InvalidVersion)));
+}
+
+#[test]
+fn not_an_object() {
+    for mut raw in [json!([]), json!("x"), json!(null), json!(1)] {
+        assert!(matches!(migrate(&mut raw), Err(Error::InvalidConfig)));
+        assert!(matches!(load(&mut raw), Err(Error::InvalidConfig)));
+    }
+}
+
+#[test]
+fn validation() {
+    let mut config = Config::new(Cipher::Aes128Ctr, None);
+    config
+        .tokens
+        .push(Token::new("token".into(), SECRET.to_vec()));
+    config.validate().unwrap();
+
+    config.tokens[0].kind = "sms".into();
+    assert!(matches!(config.validate(), Err(Error::UnknownType(_))));
+    config.tokens[0].kind = "ocra".into();
+    config.tokens[0].suite = Some("OCRA-1:HOTP-SHA1-6:QA08".into());
+    assert!(matches!(config.validate(), Err(Error::InvalidSuite(_))));
+    config.tokens[0].suite = Some("OCRA-1:HOTP-SHA1-6:QN08".into());
+    config.validate().unwrap();
+}
+
+#[test]
+fn redacted_debug() {
+    let mut token = Token::new("token".into(), b"top secret".to_vec());
+    token.pin = Some("4711".into());
+    token.prepare(&mut Software);
+    let debug = format!("{:?}", token);
+    assert!(debug.contains("\"token\""));
+    for secret in [
+        "top secret",
+        "116, 111, 112",
+        "4711",
+        "secret:",
+        "pin:",
+        "hmac_key",
+    ] {
+        assert!(!debug.contains(secret), "{}", debug);
+    }
+}
+
+#[test]
+fn ocra_token() {
+    const SUITE: &str = "OCRA-1:HOTP-SHA1-6:QN08";
+    let mut token = Token::new("ocra".into(), SECRET.to_vec());
+    token.kind = "ocra".into();
+    token.suite = Some(SUITE.into());
+    let mut config = Config::new(Cipher::Aes128Gcm, None);
+    config.tokens.push(token);
+    config.encrypt(&mut Software, PIN, &mut counter()).unwrap();
+
+    // the suite is parsed when the config is loaded
+    let mut config = Config::from_value(serde_json::to_value(&config).unwrap()).unwrap();
+    assert_eq!(config.tokens[0].ocra_suite().unwrap().as_str(), SUITE);
+    config.decrypt(&mut Software, PIN).unwrap();
+    let token = &mut config.tokens[0];
+    token.prepare(&mut Software);
+    assert_eq!(token.otp().challenge_length(token), 8);
+    // RFC 6287, one-way challenge-response with the 20 byte seed
+    let code = token.code(&mut Software, 0, "00000000").unwrap();
+    assert_eq!(code.to_string(), "237653");
+    assert_eq!(
+        token.code(&mut Software, 0, "0000000a").err(),
+        Some(algorithm::Error::Ocra(ocra::Error::InvalidChallenge))
+    );
+}
+
+#[test]
+fn plaintext_keys() {
+    for cipher in [Cipher::Aes128Ctr, Cipher::Aes128Gcm] {
+        // a URI added by hand between the encrypted keys
+        let mut raw = serde_json::to_value(store(cipher, None)).unwrap();
+        raw["tokens"]
+            .as_array_mut()
+            .unwrap()
+            .insert(1, "otpauth://totp/Example:alice?secret=jbsw y3dp".into());
+        let (mut config, _) = load(&mut raw).unwrap();
+        assert!(config.tokens[1].plaintext);
+        assert_eq!(raw["tokens"][1]["plaintext"], true);
+        assert_eq!(config.needs_encryption(), cipher == Cipher::Aes128Gcm);
+
+        config.decrypt(&mut Software, PIN).unwrap();
+        let secrets: Vec<&[u8]> = config.tokens.iter().map(|t| t.secret.as_slice()).collect();
+        assert_eq!(
+            secrets,
+            [SECRET, b"Hello", b"another secret"],
+            "{:?}",
+            cipher
+        );
+
+        config.encrypt(&mut Software, PIN, &mut counter()).unwrap();
+        assert!(!config.tokens[1].plaintext);
+        let raw = serde_json::to_value(&config).unwrap();
+        assert!(raw["tokens"][1].get("plaintext").is_none());
+        let mut config = Config::from_value(raw).unwrap();
+        config.decrypt(&mut Software, PIN).unwrap();
+        assert_eq!(config.tokens[1].secret, b"Hello");
+    }
+
+    // only URIs, the PIN they are decrypted with first becomes the PIN
+    let mut raw = json!({"version": VERSION, "cipher": "aes-128-ctr", "tokens": [
+        "otpauth://totp/alice?secret=JBSWY3DP",
+    ]});
+    let (mut config, _) = load(&mut raw).unwrap();
+    assert!(config.needs_encryption());
+    config.decrypt(&mut Software, PIN).unwrap();
+    assert_eq!(config.tokens[0].secret, b"Hello");
+}
+
+#[test]
+fn stored_counters() {
+    let mut config = Config::new(Cipher::Aes128Gcm, None);
+    for (name, kind, counter) in [
+        ("totp", "totp", 0),
+        ("hotp", "hotp", 3),
+        ("resync", "hotp", 9),
+    ] {
+        let mut token = Token::new(name.into(), SECRET.to_vec());
+        token.kind = kind.into();
+        token.counter = counter;
+        config.tokens.push(token);
+    }
+    let mut counters = config.counters();
+    assert_eq!(counters.keys().collect::<Vec<_>>(), ["hotp", "resync"]);
+
+    // the counters written by the device, the config was changed in between
+    counters.insert("hotp".into(), 5);
+    counters.insert("resync".into(), 7);
+    counters.insert("removed".into(), 1);
+    config.apply_counters(&counters);
+    let counters: Vec<u64> = config.tokens.iter().map(|token| token.counter).collect();
+    assert_eq!(counters, [0, 5, 9]);
+}
+
+#[test]
+fn failed_decrypt() {
+    // a malformed plaintext key next to an encrypted one
+    let mut raw = serde_json::to_value(store(Cipher::Aes128Gcm, None)).unwrap();
+    raw["tokens"][1] = json!({"name": "b", "plaintext": true, "key": "JBSW1"});
+    let (mut config, _) = load(&mut raw).unwrap();
+    let keys: Vec<String> = config.tokens.iter().map(|t| t.key.clone()).collect();
+    assert!(matches!(
+        config.decrypt(&mut Software, PIN),
+        Err(Error::InvalidKey(name)) if name == "b"
+    ));
+
+    // none of the secrets is known, encrypting them would replace the stored keys
+    assert!(config.tokens.iter().all(|token| token.secret.is_empty()));
+    let stored: Vec<String> = config.tokens.iter().map(|t| t.key.clone()).collect();
+    assert_eq!(stored, keys);
+    config.tokens.truncate(1);
+    config.decrypt(&mut Software, PIN).unwrap();
+    assert_eq!(config.tokens[0].secret, SECRET);
+}
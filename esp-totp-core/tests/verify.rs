@@ -0,0 +1,59 @@
+//! Verification of entered codes against a window of time steps.
+use esp_totp_core::otp::{steam, totp, verify};
+use esp_totp_core::software::Software;
+use esp_totp_core::{Algorithm, HmacKey};
+
+const SECRET: &[u8] = b"12345678901234567890";
+
+fn check(code: &str, timestamp: u64, window: u64) -> Option<i64> {
+    let key = HmacKey::new(&mut Software, Algorithm::Sha1, SECRET);
+    verify(code, timestamp, 30, window, |t| {
+        Some(totp(&mut Software, &key, 8, 30, t))
+    })
+}
+
+#[test]
+fn current_step() {
+    assert_eq!(check("94287082", 59, 0), Some(0));
+    assert_eq!(check("94287082", 59, 2), Some(0));
+}
+
+#[test]
+fn window() {
+    // the code of 1111111109 is one step before 1111111111
+    assert_eq!(check("07081804", 1111111111, 0), None);
+    assert_eq!(check("07081804", 1111111111, 1), Some(-1));
+    assert_eq!(check("14050471", 1111111109, 1), Some(1));
+    assert_eq!(check("14050471", 1111111109 - 30, 1), None);
+    assert_eq!(check("14050471", 1111111109 - 30, 2), Some(2));
+}
+
+#[test]
+fn invalid() {
+    assert_eq!(check("12345678", 59, 3), None);
+    // the length has to match as well
+    assert_eq!(check("9428708", 59, 0), None);
+    assert_eq!(check("942870820", 59, 0), None);
+}
+
+#[test]
+fn before_unix_epoch() {
+    // the steps before the unix epoch are skipped
+    assert_eq!(check("94287082", 10, 5), Some(1));
+}
+
+#[test]
+fn missing_tokens() {
+    assert_eq!(verify("94287082", 59, 30, 3, |_| None), None);
+}
+
+#[test]
+fn steam_codes() {
+    let key = HmacKey::new(&mut Software, Algorithm::Sha1, b"superdupersecret");
+    let result = verify("94R9D", 3000030, 30, 1, |t| {
+        Some(steam(&mut Software, &key, t))
+    });
+    assert_eq!(result, Some(-1));
+    assert!(steam(&mut Software, &key, 3000030).matches("YRGQJ"));
+    assert!(!steam(&mut Software, &key, 3000030).matches("yrgqj"));
+}
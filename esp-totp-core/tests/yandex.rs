@@ -0,0 +1,32 @@
+//! Yandex Key codes, the vectors are taken from the Aegis test suite.
+use data_encoding::BASE32_NOPAD;
+use esp_totp_core::otp::{yandex, yandex_key};
+use esp_totp_core::software::Software;
+
+const VECTORS: [(&str, &str, u64, &str); 5] = [
+    ("5239", "6SB2IKNM6OBZPAVBVTOHDKS4FAAAAAAADFUTQMBTRY", 1641559648, "umozdicq"),
+    ("7586", "LA2V6KMCGYMWWVEW64RNP3JA3IAAAAAAHTSG4HRZPI", 1581064020, "oactmacq"),
+    ("7586", "LA2V6KMCGYMWWVEW64RNP3JA3IAAAAAAHTSG4HRZPI", 1581090810, "wemdwrix"),
+    ("5210481216086702", "JBGSAU4G7IEZG6OY4UAXX62JU4AAAAAAHTSG4HXU3M", 1581091469, "dfrpywob"),
+    ("5210481216086702", "JBGSAU4G7IEZG6OY4UAXX62JU4AAAAAAHTSG4HXU3M", 1581093059, "vunyprpd"),
+];
+
+#[test]
+fn codes() {
+    for (pin, secret, timestamp, expected) in VECTORS {
+        let secret = BASE32_NOPAD.decode(secret.as_bytes()).unwrap();
+        let key = yandex_key(&mut Software, &secret, pin);
+        let token = yandex(&mut Software, &key, timestamp);
+        assert_eq!(token.to_string(), expected, "timestamp {}", timestamp);
+    }
+}
+
+#[test]
+fn without_checksum() {
+    // only the first 16 bytes of the secret are used
+    let (pin, secret, timestamp, expected) = VECTORS[0];
+    let secret = BASE32_NOPAD.decode(secret.as_bytes()).unwrap();
+    let key = yandex_key(&mut Software, &secret[..16], pin);
+    let token = yandex(&mut Software, &key, timestamp);
+    assert_eq!(token.to_string(), expected);
+}
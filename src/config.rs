@@ -1,118 +1,192 @@
-use alloc::{format};
+use alloc::format;
 use alloc::string::String;
 use alloc::vec::Vec;
+use core::fmt;
 
-use aes::cipher::{KeyIvInit, StreamCipher};
-use data_encoding::BASE32_NOPAD;
-use embedded_sdmmc::Mode;
+use embedded_hal_bus::spi::ExclusiveDevice;
+use embedded_sdmmc::{Mode, VolumeIdx, VolumeManager};
 use esp_hal::clock::Clocks;
 use esp_hal::delay::Delay;
-use esp_hal::gpio::{InputPin, Level, NO_PIN, Output, OutputPin};
+use esp_hal::gpio::{AnyOutput, InputPin, Level, NO_PIN, OutputPin};
 use esp_hal::peripheral::Peripheral;
 use esp_hal::peripherals;
 use esp_hal::prelude::_fugit_RateExtU32;
 use esp_hal::spi::master::Spi;
-use esp_hal::spi::SpiMode;
-use serde::Deserialize;
+use esp_hal::spi::{FullDuplexMode, SpiMode};
+pub(crate) use esp_totp_core::vault::{Config, Token};
+use esp_totp_core::vault::{self, Counters, COUNTER_FILE};
 
-#[derive(Deserialize, Debug, Clone)]
-pub(crate) struct Token {
-    pub(crate) name: String,
-    pub(crate) key: String,
+use crate::rtc::Rtc;
+
+#[derive(Debug)]
+pub(crate) enum Error {
+    /// The SD-Card did not respond or has no FAT volume, e.g. because it is not inserted.
+    Card(String),
+    /// There is no config file on the SD-Card.
+    NotFound,
+    SD(String),
+    Deserialize(serde_json::Error),
+    Serialize(serde_json::Error),
+    /// The content of the config is invalid.
+    Vault(vault::Error),
 }
 
-impl Token {
-    pub(crate) fn key_as_bytes(&self) -> Vec<u8> {
-        BASE32_NOPAD.decode(self.key.as_bytes()).unwrap()
+impl Error {
+    /// Short description of the error, fits into a line of the display.
+    pub(crate) fn message(&self) -> &'static str {
+        match self {
+            Error::Card(_) => "No SD-Card",
+            Error::NotFound => "No CFG file",
+            Error::SD(_) => "SD-Card error",
+            // written by a newer esp-totp-cfg, the firmware has to be updated
+            Error::Vault(vault::Error::UnsupportedVersion(_)) => "Config too new",
+            Error::Deserialize(_) | Error::Vault(_) => "Invalid config",
+            Error::Serialize(_) => "Write failed",
+        }
     }
 }
 
-#[derive(Deserialize, Debug, Clone)]
-pub(crate) struct Config {
-    pub(crate) nonce: Option<String>,
-    pub(crate) tokens: Vec<Token>,
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
 }
 
-#[derive(Debug)]
-pub(crate) enum Error {
-    SD(String),
-    Deserialize(serde_json::Error),
-    MissingIV,
-    InvalidIV,
-    Base32(data_encoding::DecodeError),
+type Result<T> = core::result::Result<T, Error>;
+
+const CONFIG_FILE: &str = "CFG";
+
+type SdCard = embedded_sdmmc::SdCard<
+    ExclusiveDevice<Spi<'static, peripherals::SPI2, FullDuplexMode>, AnyOutput<'static>, Delay>,
+    Delay,
+>;
+
+/// Keeps the SD-Card accessible after the config was loaded, to write back HOTP counters.
+pub(crate) struct Storage {
+    volume_mgr: VolumeManager<SdCard, Rtc>,
+    // the counters as they are stored on the SD-Card
+    counters: Counters,
 }
 
-type Result<T> = core::result::Result<T, Error>;
+impl Storage {
+    pub(crate) fn new<SCK: OutputPin, MOSI: OutputPin, MISO: InputPin, CS: OutputPin>(
+        spi2: impl Peripheral<P = peripherals::SPI2> + 'static,
+        sck: impl Peripheral<P = SCK> + 'static,
+        mosi: impl Peripheral<P = MOSI> + 'static,
+        miso: impl Peripheral<P = MISO> + 'static,
+        cs: impl Peripheral<P = CS> + 'static,
+        clocks: &Clocks,
+        delay: Delay,
+        rtc: Rtc,
+    ) -> Self {
+        let spi = Spi::new(spi2, 400.kHz(), SpiMode::Mode0, clocks).with_pins(
+            Some(sck),
+            Some(mosi),
+            Some(miso),
+            NO_PIN,
+        );
+        // setting a GPIO can not fail, the errors of the card show up when it is read
+        let spi_dev = ExclusiveDevice::new(spi, AnyOutput::new(cs, Level::Low), delay)
+            .unwrap_or_else(|never| match never {});
 
-pub(crate) fn load_config<SCK: OutputPin, MOSI: OutputPin, MISO: InputPin, CS: OutputPin>(
-    spi2: impl Peripheral<P = peripherals::SPI2>,
-    sck: impl Peripheral<P = SCK>,
-    mosi: impl Peripheral<P = MOSI>,
-    miso: impl Peripheral<P = MISO>,
-    cs: impl Peripheral<P = CS>,
-    clocks: &Clocks,
-    delay: Delay,
-    rtc: &crate::rtc::Rtc,
-) -> Result<Config> {
-    let spi = Spi::new(spi2, 400.kHz(), SpiMode::Mode0, clocks).with_pins(
-        Some(sck),
-        Some(mosi),
-        Some(miso),
-        NO_PIN,
-    );
-    let spi_dev =
-        embedded_hal_bus::spi::ExclusiveDevice::new(spi, Output::new(cs, Level::Low), delay)
-            .unwrap();
-
-    let sdcard = embedded_sdmmc::SdCard::new(spi_dev, delay);
-    let mut volume_mgr = embedded_sdmmc::VolumeManager::new(sdcard, rtc);
-    let mut volume0 = volume_mgr
-        .open_volume(embedded_sdmmc::VolumeIdx(0))
-        .unwrap();
-    // Open the root directory (mutably borrows from the volume).
-    let mut root_dir = volume0.open_root_dir().unwrap();
-    let mut file = root_dir
-        .open_file_in_dir("CFG", Mode::ReadOnly)
-        .map_err(|err| Error::SD(format!("A {:?}", err)))?;
-    let mut data = Vec::with_capacity(file.length() as usize);
-    while !file.is_eof() {
-        let mut buffer = [0u8; 64];
-        let len = file
-            .read(&mut buffer)
-            .map_err(|err| Error::SD(format!("{:?}", err)))?;
-        data.extend_from_slice(&buffer[..len]);
+        let sdcard = embedded_sdmmc::SdCard::new(spi_dev, delay);
+        Storage {
+            volume_mgr: VolumeManager::new(sdcard, rtc),
+            counters: Counters::new(),
+        }
     }
-    serde_json::from_slice(data.as_slice()).map_err(Error::Deserialize)
-}
 
-type Aes128Ctr64LE = ctr::Ctr64LE<aes::Aes128>;
+    /// Reads the config from the SD-Card, can be called again after an error, e.g. once the
+    /// card was inserted.
+    pub(crate) fn load(&mut self) -> Result<Config> {
+        // initialize the card again, it may have been swapped
+        self.volume_mgr.device().mark_card_uninit();
+        let data = self.read(CONFIG_FILE)?;
 
-pub(crate) fn decrypt(config: &mut Config, pin: String) -> Result<()> {
-    let iv = match &config.nonce {
-        None => return Err(Error::MissingIV),
-        Some(nonce) => {
-            let nonce = BASE32_NOPAD.decode(nonce.as_bytes()).unwrap();
-            if nonce.len() != 16 {
-                return Err(Error::InvalidIV);
+        let mut raw: serde_json::Value =
+            serde_json::from_slice(data.as_slice()).map_err(Error::Deserialize)?;
+        let (mut config, migrated) = vault::load(&mut raw).map_err(Error::Vault)?;
+        // only upgrade the file if it is valid in the new version, if writing fails it is migrated
+        // again on the next start
+        if migrated {
+            if let Err(err) = self.store(&raw) {
+                log::warn!("Could not write the migrated config: {:?}", err);
             }
-            let mut iv = [0u8; 16];
-            iv.copy_from_slice(nonce.as_slice());
-            iv
         }
-    };
-    let mut key = [0u8; 16];
-    let pin = pin.as_bytes();
-    key[..pin.len()].copy_from_slice(pin);
-
-    let mut cipher = Aes128Ctr64LE::new(&key.into(), &iv.into());
-
-    for token in config.tokens.iter_mut() {
-        let mut raw = BASE32_NOPAD
-            .decode(token.key.as_bytes())
-            .map_err(Error::Base32)?;
-        cipher.apply_keystream(&mut raw);
-        token.key = BASE32_NOPAD.encode(&raw);
+
+        // a counter file cut short by a power loss only loses the counters since the last change
+        // of the config
+        self.counters = match self.read(COUNTER_FILE) {
+            Ok(data) => serde_json::from_slice(data.as_slice()).unwrap_or_else(|err| {
+                log::warn!("Ignoring the invalid counters: {:?}", err);
+                Counters::new()
+            }),
+            Err(Error::NotFound) => Counters::new(),
+            Err(err) => return Err(err),
+        };
+        config.apply_counters(&self.counters);
+        Ok(config)
+    }
+
+    /// Replaces the stored config, e.g. once its plaintext keys were encrypted.
+    pub(crate) fn store_config(&mut self, config: &Config) -> Result<()> {
+        let raw = serde_json::to_value(config).map_err(Error::Serialize)?;
+        self.store(&raw)
+    }
+
+    /// Writes the counter of the `token` to its own small file, the config with the keys is not
+    /// touched.
+    pub(crate) fn store_counter(&mut self, token: &Token) -> Result<()> {
+        self.counters.insert(token.name.clone(), token.counter);
+        let data = serde_json::to_vec(&self.counters).map_err(Error::Serialize)?;
+        self.write(COUNTER_FILE, data.as_slice())
+    }
+
+    /// Writes the config back to the SD-Card.
+    fn store(&mut self, raw: &serde_json::Value) -> Result<()> {
+        let data = serde_json::to_vec(raw).map_err(Error::Serialize)?;
+        self.write(CONFIG_FILE, data.as_slice())
     }
 
-    Ok(())
+    fn read(&mut self, name: &str) -> Result<Vec<u8>> {
+        let mut volume0 = self
+            .volume_mgr
+            .open_volume(VolumeIdx(0))
+            .map_err(|err| Error::Card(format!("{:?}", err)))?;
+        // Open the root directory (mutably borrows from the volume).
+        let mut root_dir = volume0
+            .open_root_dir()
+            .map_err(|err| Error::Card(format!("{:?}", err)))?;
+        let mut file = root_dir
+            .open_file_in_dir(name, Mode::ReadOnly)
+            .map_err(|err| match err {
+                embedded_sdmmc::Error::NotFound => Error::NotFound,
+                err => Error::SD(format!("{:?}", err)),
+            })?;
+        let mut data = Vec::with_capacity(file.length() as usize);
+        while !file.is_eof() {
+            let mut buffer = [0u8; 64];
+            let len = file
+                .read(&mut buffer)
+                .map_err(|err| Error::SD(format!("{:?}", err)))?;
+            data.extend_from_slice(&buffer[..len]);
+        }
+        Ok(data)
+    }
+
+    fn write(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        let mut volume0 = self
+            .volume_mgr
+            .open_volume(VolumeIdx(0))
+            .map_err(|err| Error::SD(format!("{:?}", err)))?;
+        let mut root_dir = volume0
+            .open_root_dir()
+            .map_err(|err| Error::SD(format!("{:?}", err)))?;
+        let mut file = root_dir
+            .open_file_in_dir(name, Mode::ReadWriteCreateOrTruncate)
+            .map_err(|err| Error::SD(format!("{:?}", err)))?;
+        file.write(data)
+            .map_err(|err| Error::SD(format!("{:?}", err)))?;
+        file.close().map_err(|err| Error::SD(format!("{:?}", err)))
+    }
 }
@@ -1,5 +1,3 @@
-use alloc::format;
-
 use esp_hal::delay::Delay;
 use esp_hal::gpio::AnyOutput;
 use hd44780_driver::{Cursor, CursorBlink, HD44780};
@@ -20,6 +18,8 @@ pub(crate) struct Display<'d> {
 }
 
 impl<'d> Display<'d> {
+    pub(crate) const COLUMNS: u8 = 16;
+
     pub(crate) fn new(
         rs: AnyOutput<'d>,
         en: AnyOutput<'d>,
@@ -35,26 +35,19 @@ impl<'d> Display<'d> {
         Display { display, delay }
     }
 
-    pub(crate) fn render_auth(&mut self, current: usize, digits: [i8; 6]) {
+    pub(crate) fn render_auth(&mut self, title: &str, current: usize, digits: &[i8]) {
         self.display.reset(&mut self.delay).unwrap();
         self.display.clear(&mut self.delay).unwrap();
 
-        self.display
-            .write_str("Enter Code:", &mut self.delay)
-            .unwrap();
+        self.display.write_str(title, &mut self.delay).unwrap();
 
         self.display.set_cursor_xy((0, 1), &mut self.delay).unwrap();
 
-        self.display
-            .write_str(
-                format!(
-                    "{}{}{}{}{}{}",
-                    digits[0], digits[1], digits[2], digits[3], digits[4], digits[5]
-                )
-                .as_str(),
-                &mut self.delay,
-            )
-            .unwrap();
+        for digit in digits {
+            self.display
+                .write_char((b'0' + *digit as u8) as char, &mut self.delay)
+                .unwrap();
+        }
 
         self.display
             .set_cursor_xy((current as u8, 1), &mut self.delay)
@@ -67,6 +60,14 @@ impl<'d> Display<'d> {
             .unwrap();
         self.display.write_str(text, &mut self.delay).unwrap();
     }
+
+    pub(crate) fn write_bar(&mut self, position: (u8, u8), width: u8, filled: u8) {
+        let (x, y) = position;
+        for i in 0..width {
+            self.write((x + i, y), if i < filled { "*" } else { " " });
+        }
+    }
+
     pub(crate) fn write_clear(&mut self, position: (u8, u8), text: &str) {
         self.clear();
         self.write(position, text);
@@ -4,8 +4,11 @@
 extern crate alloc;
 
 use alloc::format;
-use alloc::string::String;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
 use core::cell::RefCell;
+use core::fmt;
 use core::ops::{Deref, DerefMut};
 
 use critical_section::{CriticalSection, Mutex};
@@ -20,13 +23,16 @@ use esp_hal::gpio::{AnyOutput, Event, Input, Io, Level, Pull};
 use esp_hal::i2c::I2C;
 use esp_hal::interrupt::Priority;
 use esp_hal::peripherals::{ADC2, Interrupt, TIMG0};
+use esp_hal::rng::Rng;
 use esp_hal::timer::timg::{Timer, Timer0, TimerGroup};
+use esp_totp_core::algorithm::Trigger;
+use esp_totp_core::otp::{self, Token};
+use esp_totp_core::vault;
 use rotary_encoder_embedded::{Direction, RotaryEncoder};
 use rotary_encoder_embedded::standard::StandardMode;
 
-use crate::config::Config;
+use crate::config::{Config, Storage};
 use crate::display::Display;
-use crate::topt::Token;
 
 mod config;
 mod display;
@@ -42,23 +48,92 @@ fn init_psram_heap() {
     }
 }
 
-#[derive(Default)]
+/// What the digits entered in [`Mode::Auth`] are used for.
+enum Purpose {
+    /// PIN to decrypt the config
+    Vault,
+    /// PIN of the current mOTP, Yandex or OCRA token, the app mode continues afterwards
+    TokenPin(AppParams),
+    /// Challenge of the current OCRA token, the response is shown in app mode afterwards
+    Challenge(AppParams),
+    /// Code to check against the current TOTP token, the result is shown in app mode afterwards
+    Verify(AppParams),
+}
+
 struct AuthParams {
     current: usize,
-    digits: [i8; 6],
+    digits: Vec<i8>,
+    purpose: Purpose,
+    /// Shown instead of the title until the digits are entered, e.g. why the config could not
+    /// be decrypted.
+    error: Option<&'static str>,
+}
+
+impl AuthParams {
+    fn new(len: usize, purpose: Purpose) -> Self {
+        AuthParams {
+            current: 0,
+            digits: vec![0i8; len],
+            purpose,
+            error: None,
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        if let Some(error) = self.error {
+            return error;
+        }
+        match self.purpose {
+            Purpose::Vault => "Enter Code:",
+            Purpose::TokenPin(_) => "Enter PIN:",
+            Purpose::Challenge(_) => "Challenge:",
+            Purpose::Verify(_) => "Verify Code:",
+        }
+    }
 }
 
 struct AppParams {
+    /// Index of the shown token, `token_len` is the entry of the overview.
     current: usize,
     token_len: usize,
     last_token: Option<Token>,
+    /// Preview of the code following `last_token`.
+    next_token: Option<Token>,
     bar: u8,
 }
 
+/// Number of tokens on a page of the overview, one per line.
+const OVERVIEW_ROWS: usize = 2;
+
+struct OverviewParams {
+    page: usize,
+    /// Codes of all tokens, `None` for tokens without a time based code.
+    codes: Vec<Option<Token>>,
+    /// All codes are computed again once the first of them expires.
+    valid_until: u64,
+    drawn: bool,
+}
+
+impl OverviewParams {
+    fn new(token_len: usize) -> Self {
+        OverviewParams {
+            page: 0,
+            codes: (0..token_len).map(|_| None).collect(),
+            valid_until: 0,
+            drawn: false,
+        }
+    }
+
+    fn pages(&self) -> usize {
+        self.codes.len().div_ceil(OVERVIEW_ROWS)
+    }
+}
+
 enum Mode {
     Init,
     Auth(AuthParams),
     App(AppParams),
+    Overview(OverviewParams),
 }
 
 impl Mode {
@@ -72,10 +147,17 @@ impl Mode {
             }
             Self::App(params) => {
                 params.current += 1;
-                if params.current >= params.token_len {
+                if params.current > params.token_len {
                     params.current = 0;
                 }
             }
+            Self::Overview(params) => {
+                params.page += 1;
+                if params.page >= params.pages() {
+                    params.page = 0;
+                }
+                params.drawn = false;
+            }
             _ => {}
         }
     }
@@ -90,9 +172,16 @@ impl Mode {
             }
             Self::App(params) => {
                 params.current = match params.current.checked_sub(1) {
-                    None => params.token_len - 1,
+                    None => params.token_len,
+                    Some(v) => v,
+                };
+            }
+            Self::Overview(params) => {
+                params.page = match params.page.checked_sub(1) {
+                    None => params.pages().saturating_sub(1),
                     Some(v) => v,
                 };
+                params.drawn = false;
             }
             _ => {}
         }
@@ -101,15 +190,7 @@ impl Mode {
     fn advance(&mut self) -> Option<String> {
         if let Self::Auth(params) = self {
             if params.current == params.digits.len() - 1 {
-                return Some(format!(
-                    "{}{}{}{}{}{}",
-                    params.digits[0],
-                    params.digits[1],
-                    params.digits[2],
-                    params.digits[3],
-                    params.digits[4],
-                    params.digits[5]
-                ));
+                return Some(params.digits.iter().map(|d| (b'0' + *d as u8) as char).collect());
             }
             params.current += 1;
         }
@@ -129,9 +210,13 @@ static ROTARY_SWITCH_DEBOUNCE: GlobalOpt<fugit::Instant<u64, 1, 1_000_000>> =
 
 static MODE: Global<Mode> = Mutex::new(RefCell::new(Mode::Init));
 static CONFIG: GlobalOpt<Config> = Mutex::new(RefCell::new(None));
+static STORAGE: GlobalOpt<Storage> = Mutex::new(RefCell::new(None));
 
 static TOTP_GEN: GlobalOpt<topt::Generator> = Mutex::new(RefCell::new(None));
 
+/// Nonces of the keys encrypted on the device.
+static RNG: GlobalOpt<Rng> = Mutex::new(RefCell::new(None));
+
 static TIMER0: GlobalOpt<Timer<Timer0<TIMG0>, Blocking>> = Mutex::new(RefCell::new(None));
 
 static DISPLAY: GlobalOpt<Display> = Mutex::new(RefCell::new(None));
@@ -175,7 +260,7 @@ fn main() -> ! {
     //
     // Init Display
     //
-    let display = Display::new(
+    let mut display = Display::new(
         AnyOutput::new(io.pins.gpio46, Level::Low), // RS
         AnyOutput::new(io.pins.gpio13, Level::Low), // EN
         AnyOutput::new(io.pins.gpio12, Level::Low),
@@ -184,9 +269,12 @@ fn main() -> ! {
         AnyOutput::new(io.pins.gpio9, Level::Low),
         delay,
     );
-    critical_section::with(|cs| {
-        DISPLAY.borrow_ref_mut(cs).replace(display);
-    });
+    // the button is polled to retry after an error at boot, it is listened to once the config
+    // was loaded
+    let mut sw = Input::new(io.pins.gpio1, Pull::Up); // Green
+
+    // the time is needed for every code, so check the RTC before asking for the PIN
+    retry(&mut display, &sw, &delay, || clock.datetime());
 
     //
     // ADC
@@ -205,7 +293,7 @@ fn main() -> ! {
     //
     // Load Config from SD-Card
     //
-    let config = config::load_config(
+    let mut storage = Storage::new(
         peripherals.SPI2,
         io.pins.gpio18, // Purple
         io.pins.gpio17, // Green
@@ -213,11 +301,14 @@ fn main() -> ! {
         io.pins.gpio16, // Orange
         &clocks,
         delay,
-        &clock,
-    )
-    .unwrap();
+        clock,
+    );
+    // a missing card or an invalid config is shown until it was fixed
+    let config = retry(&mut display, &sw, &delay, || storage.load());
     critical_section::with(|cs| {
+        DISPLAY.borrow_ref_mut(cs).replace(display);
         CONFIG.replace(cs, Some(config));
+        STORAGE.replace(cs, Some(storage));
     });
 
     //
@@ -225,6 +316,7 @@ fn main() -> ! {
     let topt_gen = topt::Generator::new(peripherals.SHA, clock);
     critical_section::with(|cs| {
         TOTP_GEN.replace(cs, Some(topt_gen));
+        RNG.replace(cs, Some(Rng::new(peripherals.RNG)));
     });
 
     //
@@ -234,7 +326,6 @@ fn main() -> ! {
     clk.listen(Event::FallingEdge);
     let mut dt = Input::new(io.pins.gpio2, Pull::Up); // Blue
     dt.listen(Event::FallingEdge);
-    let mut sw = Input::new(io.pins.gpio1, Pull::Up); // Green
     sw.listen(Event::FallingEdge);
 
     let rotary_encoder = RotaryEncoder::new(dt, clk).into_standard_mode();
@@ -261,6 +352,33 @@ fn main() -> ! {
     loop {}
 }
 
+/// Shows an error at boot until the button is pushed to try again, e.g. once the SD-Card was
+/// inserted.
+fn retry<T, E: fmt::Debug + fmt::Display>(
+    display: &mut Display,
+    switch: &Input<gpio::Gpio1>,
+    delay: &Delay,
+    mut f: impl FnMut() -> Result<T, E>,
+) -> T {
+    loop {
+        let err = match f() {
+            Ok(value) => return value,
+            Err(err) => err,
+        };
+        log::error!("{:?}", err);
+        display.write_clear((0, 0), format!("{}", err).as_str());
+        display.write((0, 1), "Push to retry");
+        // wait until the button is pushed and released again
+        while switch.is_high() {
+            delay.delay_millis(10);
+        }
+        while switch.is_low() {
+            delay.delay_millis(10);
+        }
+        display.write_clear((0, 0), "Retrying...");
+    }
+}
+
 #[handler]
 fn encoder_handler() {
     #[derive(PartialEq, Eq)]
@@ -268,7 +386,16 @@ fn encoder_handler() {
         None,
         UpdateAuth,
         UpdateToken,
+        NextCode,
+        EnterPin,
+        EnterChallenge,
+        EnterVerify,
+        EnterOverview,
+        LeaveOverview,
         Decrypt(String),
+        TokenPin(String),
+        Challenge(String),
+        Verify(String),
     }
     let action = critical_section::with(|cs| {
         let mut mode = MODE.borrow_ref_mut(cs);
@@ -298,9 +425,11 @@ fn encoder_handler() {
                     Mode::Auth(_) => Action::UpdateAuth,
                     Mode::App(state) => {
                         state.last_token = None;
+                        state.next_token = None;
                         state.bar = 0;
                         Action::UpdateToken
                     }
+                    Mode::Overview(_) => Action::UpdateToken,
                     _ => Action::None,
                 };
             }
@@ -315,18 +444,23 @@ fn encoder_handler() {
             Mode::Auth(_) => {
                 if let Some(switch) = ROTARY_SWITCH.borrow_ref_mut(cs).as_mut() {
                     if switch.is_interrupt_set() {
-                        let now = time::current_time();
-                        let last = ROTARY_SWITCH_DEBOUNCE.replace(cs, Some(now));
-                        let advance = match last {
-                            None => true,
-                            Some(last) => now - last > 250u64.millis::<1, 1_000_000>(),
-                        };
-                        if advance {
+                        if debounce(cs) {
                             next_action = match mode.advance() {
-                                Some(pin) => {
-                                    switch.unlisten(); // switch has no use anymore!
-                                    Action::Decrypt(pin)
-                                }
+                                Some(input) => match mode {
+                                    Mode::Auth(AuthParams {
+                                        purpose: Purpose::TokenPin(_),
+                                        ..
+                                    }) => Action::TokenPin(input),
+                                    Mode::Auth(AuthParams {
+                                        purpose: Purpose::Challenge(_),
+                                        ..
+                                    }) => Action::Challenge(input),
+                                    Mode::Auth(AuthParams {
+                                        purpose: Purpose::Verify(_),
+                                        ..
+                                    }) => Action::Verify(input),
+                                    _ => Action::Decrypt(input),
+                                },
                                 None => Action::UpdateAuth,
                             };
                         }
@@ -334,16 +468,55 @@ fn encoder_handler() {
                     };
                 }
             }
+            // button push in app mode generates the next code of a HOTP token, asks for the
+            // challenge of an OCRA token, asks for the PIN of a mOTP, Yandex or OCRA token or
+            // asks for a code to verify against a TOTP token, after the last token it opens the
+            // overview
+            Mode::App(state) => {
+                if let Some(switch) = ROTARY_SWITCH.borrow_ref_mut(cs).as_mut() {
+                    if switch.is_interrupt_set() {
+                        if debounce(cs) {
+                            let config = CONFIG.borrow_ref(cs);
+                            let token = config.as_ref().unwrap().tokens.get(state.current);
+                            next_action = match token {
+                                None => Action::EnterOverview,
+                                Some(token) => {
+                                    let otp = token.otp();
+                                    match otp.trigger() {
+                                        Trigger::Counter => Action::NextCode,
+                                        Trigger::Challenge
+                                            if !otp.needs_pin(token) || token.pin.is_some() =>
+                                        {
+                                            Action::EnterChallenge
+                                        }
+                                        _ if otp.needs_pin(token) => Action::EnterPin,
+                                        Trigger::Time if otp.verifiable() => Action::EnterVerify,
+                                        _ => Action::None,
+                                    }
+                                }
+                            };
+                        }
+                        switch.clear_interrupt();
+                    };
+                }
+            }
+            // button push in the overview returns to the tokens of the current page
+            Mode::Overview(_) => {
+                if let Some(switch) = ROTARY_SWITCH.borrow_ref_mut(cs).as_mut() {
+                    if switch.is_interrupt_set() {
+                        if debounce(cs) {
+                            next_action = Action::LeaveOverview;
+                        }
+                        switch.clear_interrupt();
+                    };
+                }
+            }
             Mode::Init => {
                 // switch app mode
-                *mode = Mode::Auth(AuthParams {
-                    current: 0,
-                    digits: [0i8; 6],
-                });
+                *mode = Mode::Auth(AuthParams::new(vault::PIN_LENGTH, Purpose::Vault));
                 ROTARY_SWITCH_DEBOUNCE.replace(cs, Some(time::current_time()));
                 next_action = Action::UpdateAuth;
             }
-            _ => {}
         }
         next_action
     });
@@ -353,10 +526,11 @@ fn encoder_handler() {
             let mut display = DISPLAY.borrow_ref_mut(cs);
             let mode = MODE.borrow_ref_mut(cs);
             if let Mode::Auth(params) = mode.deref() {
-                display
-                    .as_mut()
-                    .unwrap()
-                    .render_auth(params.current, params.digits);
+                display.as_mut().unwrap().render_auth(
+                    params.title(),
+                    params.current,
+                    params.digits.as_slice(),
+                );
             }
         }
         Action::Decrypt(pin) => {
@@ -366,10 +540,38 @@ fn encoder_handler() {
 
             let mut config = CONFIG.borrow_ref_mut(cs);
             let config = config.as_mut().unwrap();
+            let mut gen = TOTP_GEN.borrow_ref_mut(cs);
+            let gen = gen.as_mut().unwrap();
 
-            match config::decrypt(config, pin) {
-                Err(err) => display.write_clear((0, 0), format!("Error: {:?}", err).as_str()),
-                Ok(_) => display.write((0, 1), "Done!"),
+            match config.decrypt(gen.hasher(), pin.as_str()) {
+                // ask again, the app mode is only entered with the right PIN
+                Err(vault::Error::WrongPin) => {
+                    let params = AuthParams::new(vault::PIN_LENGTH, Purpose::Vault);
+                    display.render_auth("Wrong PIN:", params.current, params.digits.as_slice());
+                    MODE.replace(cs, Mode::Auth(params));
+                    return;
+                }
+                // the keys are still encrypted, codes of empty keys would look valid
+                Err(err) => {
+                    log::error!("Could not decrypt the config: {:?}", err);
+                    let mut params = AuthParams::new(vault::PIN_LENGTH, Purpose::Vault);
+                    params.error = Some(config::Error::Vault(err).message());
+                    display.render_auth(params.title(), params.current, params.digits.as_slice());
+                    MODE.replace(cs, Mode::Auth(params));
+                    return;
+                }
+                Ok(_) => {
+                    display.write((0, 1), "Done!");
+                    // keys added as otpauth URIs are encrypted with the entered PIN, only once
+                    // all secrets are known, otherwise the stored keys would be replaced
+                    if config.needs_encryption() {
+                        encrypt_plaintext(cs, config, gen, pin.as_str());
+                    }
+                }
+            }
+            // decode the keys once instead of on every update
+            for token in config.tokens.iter_mut() {
+                gen.prepare(token);
             }
 
             display.toggle_cursor(false);
@@ -381,6 +583,7 @@ fn encoder_handler() {
                     current: 0,
                     token_len: config.tokens.len(),
                     last_token: None,
+                    next_token: None,
                     bar: 0,
                 }),
             );
@@ -391,10 +594,289 @@ fn encoder_handler() {
             timer0.start();
         }
         Action::UpdateToken => update_token(cs),
+        Action::NextCode => next_code(cs),
+        Action::EnterPin => enter_digits(cs, Purpose::TokenPin),
+        Action::EnterChallenge => enter_digits(cs, Purpose::Challenge),
+        Action::EnterVerify => enter_digits(cs, Purpose::Verify),
+        Action::EnterOverview => {
+            let token_len = CONFIG.borrow_ref(cs).as_ref().unwrap().tokens.len();
+            MODE.replace(cs, Mode::Overview(OverviewParams::new(token_len)));
+            update_token(cs);
+        }
+        Action::LeaveOverview => {
+            let page = match MODE.borrow_ref(cs).deref() {
+                Mode::Overview(params) => params.page,
+                _ => return,
+            };
+            let token_len = CONFIG.borrow_ref(cs).as_ref().unwrap().tokens.len();
+            MODE.replace(
+                cs,
+                Mode::App(AppParams {
+                    current: (page * OVERVIEW_ROWS).min(token_len),
+                    token_len,
+                    last_token: None,
+                    next_token: None,
+                    bar: 0,
+                }),
+            );
+            update_token(cs);
+        }
+        Action::Challenge(challenge) => respond(cs, challenge),
+        Action::Verify(code) => verify(cs, code),
+        Action::TokenPin(pin) => {
+            let state = match MODE.replace(cs, Mode::Init) {
+                Mode::Auth(AuthParams {
+                    purpose: Purpose::TokenPin(state),
+                    ..
+                }) => state,
+                other => {
+                    MODE.replace(cs, other);
+                    return;
+                }
+            };
+            if let Some(config) = CONFIG.borrow_ref_mut(cs).as_mut() {
+                let token = &mut config.tokens[state.current];
+                token.pin = Some(pin);
+                TOTP_GEN.borrow_ref_mut(cs).as_mut().unwrap().prepare(token);
+            }
+            DISPLAY.borrow_ref_mut(cs).as_mut().unwrap().toggle_cursor(false);
+            MODE.replace(
+                cs,
+                Mode::App(AppParams {
+                    last_token: None,
+                    next_token: None,
+                    bar: 0,
+                    ..state
+                }),
+            );
+            update_token(cs);
+        }
         _ => {}
     });
 }
 
+/// Encrypts the plaintext keys of the decrypted `config` and writes it back to the SD-Card, if
+/// that fails the keys stay plaintext until the next start.
+fn encrypt_plaintext(
+    cs: CriticalSection,
+    config: &mut Config,
+    gen: &mut topt::Generator,
+    pin: &str,
+) {
+    let mut rng = RNG.borrow_ref_mut(cs);
+    let rng = rng.as_mut().unwrap();
+    let mut random = |buffer: &mut [u8]| {
+        for chunk in buffer.chunks_mut(4) {
+            chunk.copy_from_slice(&rng.random().to_le_bytes()[..chunk.len()]);
+        }
+    };
+    if let Err(err) = config.encrypt(gen.hasher(), pin, &mut random) {
+        log::warn!("Could not encrypt the keys: {:?}", err);
+        return;
+    }
+    let mut storage = STORAGE.borrow_ref_mut(cs);
+    if let Err(err) = storage.as_mut().unwrap().store_config(config) {
+        log::warn!("Could not write the encrypted keys: {:?}", err);
+    }
+}
+
+fn debounce(cs: CriticalSection) -> bool {
+    let now = time::current_time();
+    let last = ROTARY_SWITCH_DEBOUNCE.replace(cs, Some(now));
+    match last {
+        None => true,
+        Some(last) => now - last > 250u64.millis::<1, 1_000_000>(),
+    }
+}
+
+/// Switches from app mode to the digit entry for the PIN or challenge of the current token.
+fn enter_digits(cs: CriticalSection, purpose: fn(AppParams) -> Purpose) {
+    let mut mode = MODE.borrow_ref_mut(cs);
+    let state = match core::mem::replace(mode.deref_mut(), Mode::Init) {
+        Mode::App(state) => state,
+        other => {
+            *mode = other;
+            return;
+        }
+    };
+    let config = CONFIG.borrow_ref(cs);
+    let token = &config.as_ref().unwrap().tokens[state.current];
+    let purpose = purpose(state);
+    let len = match purpose {
+        Purpose::Challenge(_) => token.otp().challenge_length(token).min(Display::COLUMNS),
+        Purpose::Verify(_) => token.digits,
+        _ => token.pin_length,
+    };
+    let params = AuthParams::new(len as usize, purpose);
+
+    // no code updates during the digit entry
+    let mut timer0 = TIMER0.borrow_ref_mut(cs);
+    let timer0 = timer0.as_mut().unwrap();
+    if timer0.is_running() {
+        timer0.stop();
+    }
+    timer0.clear_interrupt();
+
+    let mut display = DISPLAY.borrow_ref_mut(cs);
+    let display = display.as_mut().unwrap();
+    display.render_auth(params.title(), params.current, params.digits.as_slice());
+    display.toggle_cursor(true);
+    *mode = Mode::Auth(params);
+}
+
+fn next_code(cs: CriticalSection) {
+    let mut mode = MODE.borrow_ref_mut(cs);
+    if let Mode::App(ref mut state) = mode.deref_mut() {
+        let mut config = CONFIG.borrow_ref_mut(cs);
+        let config = config.as_mut().unwrap();
+        let current = &mut config.tokens[state.current];
+        let otp = current.otp();
+        if otp.trigger() != Trigger::Counter {
+            return;
+        }
+        let mut display = DISPLAY.borrow_ref_mut(cs);
+        let display = display.as_mut().unwrap();
+        let mut gen = TOTP_GEN.borrow_ref_mut(cs);
+        let gen = gen.as_mut().unwrap();
+
+        let timestamp = match gen.timestamp() {
+            Ok(timestamp) => timestamp,
+            Err(err) => {
+                display.write_clear((0, 0), format!("{}", err).as_str());
+                return;
+            }
+        };
+        let token = match gen.code(current, timestamp, "") {
+            Ok(token) => token,
+            Err(err) => {
+                display.write_clear((0, 0), format!("Error: {:?}", err).as_str());
+                return;
+            }
+        };
+        // persist the counter before showing the code, so a code is never used twice
+        if otp.uses_counter(current) {
+            current.counter += 1;
+            let mut storage = STORAGE.borrow_ref_mut(cs);
+            if let Err(err) = storage.as_mut().unwrap().store_counter(current) {
+                display.write_clear((0, 0), format!("Error: {:?}", err).as_str());
+                return;
+            }
+        }
+        display.write((0, 1), format!("{:<16}", token.to_string()).as_str());
+        state.last_token = Some(token);
+    }
+}
+
+/// Computes the response of the current OCRA token to the entered challenge.
+fn respond(cs: CriticalSection, challenge: String) {
+    let mut state = match MODE.replace(cs, Mode::Init) {
+        Mode::Auth(AuthParams {
+            purpose: Purpose::Challenge(state),
+            ..
+        }) => state,
+        other => {
+            MODE.replace(cs, other);
+            return;
+        }
+    };
+    let mut display = DISPLAY.borrow_ref_mut(cs);
+    let display = display.as_mut().unwrap();
+    display.toggle_cursor(false);
+    let mut config = CONFIG.borrow_ref_mut(cs);
+    let config = config.as_mut().unwrap();
+    let current = &mut config.tokens[state.current];
+    display.write_clear((0, 0), current.name.as_str());
+
+    let mut gen = TOTP_GEN.borrow_ref_mut(cs);
+    let gen = gen.as_mut().unwrap();
+    let timestamp = match gen.timestamp() {
+        Ok(timestamp) => timestamp,
+        Err(err) => {
+            display.write((0, 1), format!("{}", err).as_str());
+            MODE.replace(cs, Mode::App(AppParams { bar: 0, ..state }));
+            return;
+        }
+    };
+    let result = gen.code(current, timestamp, challenge.as_str());
+    let uses_counter = current.otp().uses_counter(current);
+
+    match result {
+        Err(err) => display.write_clear((0, 0), format!("Error: {:?}", err).as_str()),
+        Ok(token) => {
+            // like HOTP, persist the counter before showing the response
+            let stored = match uses_counter {
+                true => {
+                    current.counter += 1;
+                    let mut storage = STORAGE.borrow_ref_mut(cs);
+                    storage.as_mut().unwrap().store_counter(current)
+                }
+                false => Ok(()),
+            };
+            match stored {
+                Err(err) => display.write_clear((0, 0), format!("Error: {:?}", err).as_str()),
+                Ok(_) => {
+                    display.write((0, 1), token.to_string().as_str());
+                    state.last_token = Some(token);
+                }
+            }
+        }
+    }
+    MODE.replace(cs, Mode::App(AppParams { bar: 0, ..state }));
+}
+
+/// Checks the entered code against the current TOTP token and shows the result for a few seconds.
+fn verify(cs: CriticalSection, code: String) {
+    let state = match MODE.replace(cs, Mode::Init) {
+        Mode::Auth(AuthParams {
+            purpose: Purpose::Verify(state),
+            ..
+        }) => state,
+        other => {
+            MODE.replace(cs, other);
+            return;
+        }
+    };
+    let mut display = DISPLAY.borrow_ref_mut(cs);
+    let display = display.as_mut().unwrap();
+    display.toggle_cursor(false);
+    let config = CONFIG.borrow_ref(cs);
+    let current = &config.as_ref().unwrap().tokens[state.current];
+    display.write_clear((0, 0), current.name.as_str());
+
+    let mut gen = TOTP_GEN.borrow_ref_mut(cs);
+    let gen = gen.as_mut().unwrap();
+    let result = gen.timestamp().map(|timestamp| {
+        otp::verify(
+            code.as_str(),
+            timestamp,
+            current.time_step(),
+            current.verify_window as u64,
+            |timestamp| gen.token(current, timestamp),
+        )
+    });
+    match result {
+        Ok(Some(0)) => display.write((0, 1), "Code valid"),
+        Ok(Some(offset)) => display.write((0, 1), format!("Code valid ({:+})", offset).as_str()),
+        Ok(None) => display.write((0, 1), "Code invalid"),
+        Err(err) => display.write((0, 1), format!("{}", err).as_str()),
+    }
+
+    // show the token again once the timer fires
+    MODE.replace(
+        cs,
+        Mode::App(AppParams {
+            last_token: None,
+            next_token: None,
+            bar: 0,
+            ..state
+        }),
+    );
+    let mut timer0 = TIMER0.borrow_ref_mut(cs);
+    let timer0 = timer0.as_mut().unwrap();
+    timer0.load_value(3.secs()).unwrap();
+    timer0.start();
+}
+
 fn update_token(cs: CriticalSection) {
     let mut mode = MODE.borrow_ref_mut(cs);
     // get the display
@@ -406,63 +888,58 @@ fn update_token(cs: CriticalSection) {
     match mode.deref_mut() {
         Mode::App(ref mut state) => {
             if let Some(config) = CONFIG.borrow_ref_mut(cs).deref() {
+                let mut timer0 = TIMER0.borrow_ref_mut(cs);
+                let timer0 = timer0.as_mut().unwrap();
+                if timer0.is_running() {
+                    timer0.stop();
+                }
+                timer0.clear_interrupt();
+                // the entry after the last token
+                let Some(current) = config.tokens.get(state.current) else {
+                    display.write_clear((0, 0), "All tokens");
+                    display.write((0, 1), "Push to show");
+                    return;
+                };
                 if state.last_token.is_none() {
-                    display.write_clear((0, 0), config.tokens[state.current].name.as_str());
+                    display.write_clear((0, 0), current.name.as_str());
                 }
-                // check if we need to update the token
-                let timestamp = gen.timestamp();
-                let (token, changed) = match state.last_token.take() {
-                    None => (
-                        gen.token(
-                            config.tokens[state.current].key_as_bytes().as_slice(),
-                            timestamp,
-                        ),
-                        true,
-                    ),
-                    Some(last) => {
-                        let remaining = last.valid_until as i64 - timestamp as i64;
-                        if remaining <= 0 {
-                            (
-                                gen.token(
-                                    config.tokens[state.current].key_as_bytes().as_slice(),
-                                    timestamp,
-                                ),
-                                true,
-                            )
-                        } else {
-                            (last, false)
+                match current.otp().trigger() {
+                    // nothing to update until the button is pushed, no need for the timer
+                    Trigger::Counter => {
+                        if state.last_token.is_none() {
+                            display.write((0, 1), "Push for code");
                         }
                     }
-                };
-                // write code
-                if changed {
-                    display.write((0, 1), format!("{:06}", token.code).as_str());
-                }
-                // remaining time
-                let remaining = token.valid_until as i64 - timestamp as i64;
-                let bar = match remaining {
-                    // todo: there must be a "math-solution" for that!
-                    26..=30 => 1,
-                    21..=25 => 2,
-                    16..=20 => 3,
-                    11..=15 => 4,
-                    6..=10 => 5,
-                    _ => 6,
-                };
-                if bar != state.bar {
-                    state.bar = bar;
-                    for i in 0..6 {
-                        display.write((7 + i, 1), if i < bar { "*" } else { " " });
+                    _ if current.needs_pin() && current.pin.is_none() => {
+                        if state.last_token.is_none() {
+                            display.write((0, 1), "Push for PIN");
+                        }
+                    }
+                    // the response is shown once the challenge was entered
+                    Trigger::Challenge => {
+                        if state.last_token.is_none() {
+                            display.write((0, 1), "Push to respond");
+                        }
+                    }
+                    Trigger::Time => {
+                        update_totp(display, gen, state, current);
+                        // check again in a second
+                        timer0.load_value(1.secs()).unwrap();
+                        timer0.start();
                     }
                 }
-                state.last_token = Some(token);
-                // calculate time until next update and set timer
+            }
+        }
+        Mode::Overview(ref mut params) => {
+            if let Some(config) = CONFIG.borrow_ref(cs).deref() {
                 let mut timer0 = TIMER0.borrow_ref_mut(cs);
                 let timer0 = timer0.as_mut().unwrap();
                 if timer0.is_running() {
                     timer0.stop();
                 }
                 timer0.clear_interrupt();
+                update_overview(display, gen, config, params);
+                // check again in a second
                 timer0.load_value(1.secs()).unwrap();
                 timer0.start();
             }
@@ -477,28 +954,36 @@ fn update_token(cs: CriticalSection) {
             let v = nb::block!(adc.read_oneshot(&mut adc_pin)).unwrap();
             let v = ((v as f32) * 2.0) / 1000.0;
 
-            let now = gen.datetime();
-            display.write_clear(
-                (0, 0),
-                format!(
-                    "{:02}.{:02}.{:02} {:.2}V",
-                    now.day(),
-                    now.month(),
-                    now.year(),
-                    v
-                )
-                .as_str(),
-            );
-            display.write(
-                (0, 1),
-                format!(
-                    "{:02}:{:02}:{:02} Cont.?",
-                    now.hour(),
-                    now.minute(),
-                    now.second()
-                )
-                .as_str(),
-            );
+            match gen.datetime() {
+                Ok(now) => {
+                    display.write_clear(
+                        (0, 0),
+                        format!(
+                            "{:02}.{:02}.{:02} {:.2}V",
+                            now.day(),
+                            now.month(),
+                            now.year(),
+                            v
+                        )
+                        .as_str(),
+                    );
+                    display.write(
+                        (0, 1),
+                        format!(
+                            "{:02}:{:02}:{:02} Cont.?",
+                            now.hour(),
+                            now.minute(),
+                            now.second()
+                        )
+                        .as_str(),
+                    );
+                }
+                // read again with the next update
+                Err(err) => {
+                    display.write_clear((0, 0), format!("{} {:.2}V", err, v).as_str());
+                    display.write((0, 1), "Cont.?");
+                }
+            }
             // calculate time until next update and set timer
             let mut timer0 = TIMER0.borrow_ref_mut(cs);
             let timer0 = timer0.as_mut().unwrap();
@@ -513,6 +998,118 @@ fn update_token(cs: CriticalSection) {
     }
 }
 
+/// Seconds before the end of a time step from which on the next code is shown as well.
+const NEXT_PREVIEW: i64 = 5;
+
+fn update_totp(
+    display: &mut Display,
+    gen: &mut topt::Generator,
+    state: &mut AppParams,
+    current: &config::Token,
+) {
+    // check if we need to update the token
+    let timestamp = match gen.timestamp() {
+        Ok(timestamp) => timestamp,
+        // show the error instead of the code until the clock can be read again
+        Err(err) => {
+            state.last_token = None;
+            state.next_token = None;
+            state.bar = 0;
+            let width = Display::COLUMNS as usize;
+            display.write((0, 1), format!("{:<width$}", err).as_str());
+            return;
+        }
+    };
+    let (token, changed) = match state.last_token.take() {
+        Some(last) if last.valid_until > timestamp => (last, false),
+        _ => {
+            let next = state.next_token.take();
+            if next.is_some() {
+                // restore the name line
+                let width = Display::COLUMNS as usize;
+                display.write((0, 0), format!("{:<width$.width$}", current.name).as_str());
+            }
+            // the previewed code becomes the current one
+            let token = match next {
+                Some(next) if next.valid_until > timestamp => next,
+                _ => match gen.token(current, timestamp) {
+                    Some(token) => token,
+                    None => return,
+                },
+            };
+            (token, true)
+        }
+    };
+    // write code
+    if changed {
+        display.write((0, 1), token.to_string().as_str());
+    }
+    // remaining time, the bar is placed right of the code and takes up to six columns
+    let remaining = token.valid_until as i64 - timestamp as i64;
+    let bar_start = token.digits + 1;
+    let bar_width = (Display::COLUMNS - bar_start).min(6);
+    // the bar fills up in equal steps over the period of the token, a time shift before `t0`
+    // saturates at the first step, which is then valid for longer than a period
+    let time_step = current.time_step() as i64;
+    let left = remaining.clamp(1, time_step);
+    let bar = bar_width - ((left - 1) * bar_width as i64 / time_step) as u8;
+    if bar != state.bar {
+        state.bar = bar;
+        display.write_bar((bar_start, 1), bar_width, bar);
+    }
+    // shortly before the code expires, show the next one right of the name
+    if remaining <= NEXT_PREVIEW && state.next_token.is_none() {
+        if let Some(next) = gen.token(current, token.valid_until) {
+            let width = (Display::COLUMNS - next.digits - 1) as usize;
+            display.write(
+                (0, 0),
+                format!("{:<width$.width$}>{}", current.name, next).as_str(),
+            );
+            state.next_token = Some(next);
+        }
+    }
+    state.last_token = Some(token);
+}
+
+fn update_overview(
+    display: &mut Display,
+    gen: &mut topt::Generator,
+    config: &Config,
+    params: &mut OverviewParams,
+) {
+    let timestamp = match gen.timestamp() {
+        Ok(timestamp) => timestamp,
+        Err(err) => {
+            display.write_clear((0, 0), format!("{}", err).as_str());
+            params.drawn = false;
+            return;
+        }
+    };
+    if params.valid_until <= timestamp {
+        params.valid_until = gen.tokens(&config.tokens, timestamp, &mut params.codes);
+        params.drawn = false;
+    }
+    if params.drawn {
+        return;
+    }
+    display.clear();
+    let first = params.page * OVERVIEW_ROWS;
+    let rows = config.tokens.iter().zip(params.codes.iter()).skip(first);
+    for (row, (token, code)) in rows.take(OVERVIEW_ROWS).enumerate() {
+        let code = match code {
+            Some(code) => code.to_string(),
+            None => "-".to_string(),
+        };
+        // the name is cut to fit the code into the same line
+        let width = Display::COLUMNS as usize - code.len() - 1;
+        display.write(
+            (0, row as u8),
+            format!("{:<width$.width$} {}", token.name, code).as_str(),
+        );
+    }
+    params.drawn = true;
+}
+
 #[handler]
 fn topt_handler() {
     critical_section::with(update_token);
@@ -1,5 +1,9 @@
+use alloc::format;
+use alloc::string::String;
 use core::cell::RefCell;
+use core::fmt;
 
+use critical_section::Mutex;
 use ds323x::interface::I2cInterface;
 use ds323x::{ic, DateTimeAccess, Datelike, Ds323x, NaiveDateTime, Timelike};
 use embedded_sdmmc::{TimeSource, Timestamp};
@@ -7,26 +11,57 @@ use esp_hal::i2c::I2C;
 use esp_hal::peripherals::I2C0;
 use esp_hal::Blocking;
 
-pub(crate) struct Rtc<'d> {
-    rtc: RefCell<Ds323x<I2cInterface<I2C<'d, I2C0, Blocking>>, ic::DS3231>>,
+type Ds3231 = Ds323x<I2cInterface<I2C<'static, I2C0, Blocking>>, ic::DS3231>;
+
+#[derive(Debug)]
+pub(crate) enum Error {
+    /// The DS3231 did not answer on the I2C bus, e.g. because of a loose wire.
+    Bus(String),
+    /// The time registers do not hold a valid date, the clock has to be set.
+    InvalidTime,
 }
 
-impl<'d> Rtc<'d> {
-    pub(crate) fn new(i2c: I2C<'d, I2C0, Blocking>) -> Self {
-        Rtc {
-            rtc: RefCell::new(Ds323x::new_ds3231(i2c)),
-        }
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Error::Bus(_) => "RTC not found",
+            Error::InvalidTime => "RTC time invalid",
+        })
     }
-    pub(crate) fn datetime(&self) -> NaiveDateTime {
-        let mut rtc = self.rtc.borrow_mut();
-        rtc.datetime().unwrap()
+}
+
+static DS3231: Mutex<RefCell<Option<Ds3231>>> = Mutex::new(RefCell::new(None));
+
+/// Handle to the DS3231, it can be copied to share the clock between the TOTP generator and the
+/// SD-Card.
+#[derive(Clone, Copy)]
+pub(crate) struct Rtc {
+    _private: (),
+}
+
+impl Rtc {
+    pub(crate) fn new(i2c: I2C<'static, I2C0, Blocking>) -> Self {
+        critical_section::with(|cs| {
+            DS3231.replace(cs, Some(Ds323x::new_ds3231(i2c)));
+        });
+        Rtc { _private: () }
+    }
+
+    pub(crate) fn datetime(&self) -> Result<NaiveDateTime, Error> {
+        let dt = critical_section::with(|cs| {
+            let mut rtc = DS3231.borrow_ref_mut(cs);
+            rtc.as_mut().unwrap().datetime()
+        });
+        dt.map_err(|err| match err {
+            ds323x::Error::Comm(err) => Error::Bus(format!("{:?}", err)),
+            _ => Error::InvalidTime,
+        })
     }
 }
 
-impl<'d> TimeSource for &Rtc<'d> {
+impl TimeSource for Rtc {
     fn get_timestamp(&self) -> Timestamp {
-        let mut rtc = self.rtc.borrow_mut();
-        let dt = match rtc.datetime() {
+        let dt = match self.datetime() {
             Err(_) => {
                 return Timestamp {
                     year_since_1970: 0,
@@ -1,89 +1,118 @@
-use alloc::vec::Vec;
-
 use ds323x::NaiveDateTime;
 use esp_hal::{Blocking, peripherals};
-use esp_hal::peripheral::Peripheral;
+use esp_hal::peripheral::{Peripheral, PeripheralRef};
 use esp_hal::prelude::nb::block;
 use esp_hal::sha::{Sha, ShaMode};
+use esp_totp_core::algorithm::{self, Trigger};
+use esp_totp_core::hash::Digest;
+use esp_totp_core::otp::Token;
+use esp_totp_core::{Algorithm, Hasher};
+
+use crate::config;
+use crate::rtc::{self, Rtc};
+
+/// [`Hasher`] backed by the SHA peripheral.
+pub(crate) struct EspSha<'a> {
+    sha: PeripheralRef<'a, peripherals::SHA>,
+}
 
-use crate::rtc::Rtc;
+impl<'a> EspSha<'a> {
+    pub(crate) fn new(sha: impl Peripheral<P = peripherals::SHA> + 'a) -> Self {
+        EspSha {
+            sha: sha.into_ref(),
+        }
+    }
+}
 
-pub(crate) struct Token {
-    pub(crate) code: u32,
-    pub(crate) valid_until: u64,
+impl<'a> Hasher for EspSha<'a> {
+    fn digest(&mut self, algorithm: Algorithm, parts: &[&[u8]]) -> Digest {
+        let mode = match algorithm {
+            Algorithm::Sha1 => ShaMode::SHA1,
+            Algorithm::Sha256 => ShaMode::SHA256,
+            Algorithm::Sha512 => ShaMode::SHA512,
+        };
+        let mut hasher: Sha<Blocking> = Sha::new(self.sha.reborrow(), mode);
+        for part in parts {
+            let mut remaining = *part;
+            while !remaining.is_empty() {
+                remaining = block!(hasher.update(remaining)).unwrap();
+            }
+        }
+        let mut output = Digest::new(algorithm);
+        block!(hasher.finish(output.as_mut_slice())).unwrap();
+        output
+    }
 }
 
 pub(crate) struct Generator<'a> {
-    hasher: Sha<'a, Blocking>,
-    rtc: Rtc<'a>,
+    hasher: EspSha<'a>,
+    rtc: Rtc,
 }
 
 impl<'a> Generator<'a> {
-    pub(crate) fn new(sha: impl Peripheral<P = peripherals::SHA> + 'a, rtc: Rtc<'a>) -> Self {
+    pub(crate) fn new(sha: impl Peripheral<P = peripherals::SHA> + 'a, rtc: Rtc) -> Self {
         Generator {
-            hasher: Sha::new(sha, ShaMode::SHA1),
+            hasher: EspSha::new(sha),
             rtc,
         }
     }
 
-    const IPAD: u8 = 0x36;
-    const OPAD: u8 = 0x5C;
-    const BLOCK_SIZE: usize = 64;
-    pub(crate) fn token(&mut self, key: &[u8], timestamp: u64) -> Token {
-        let mut key_padded = [0u8; Self::BLOCK_SIZE];
-        if key.len() > Self::BLOCK_SIZE {
-            let key_hash = self.hash(key);
-            key_padded[..key.len()].copy_from_slice(&key_hash);
-        } else {
-            key_padded[..key.len()].copy_from_slice(key);
-        }
-
-        let mut ipad_key = key_padded;
-        for b in ipad_key.iter_mut() {
-            *b ^= Self::IPAD;
-        }
-        let t = timestamp / 30;
-        let msg = t.to_be_bytes();
-
-        let mut content = Vec::with_capacity(ipad_key.len() + msg.len());
-        content.extend_from_slice(&ipad_key);
-        content.extend_from_slice(&msg);
+    /// The SHA peripheral, e.g. to derive the key of the config.
+    pub(crate) fn hasher(&mut self) -> &mut dyn Hasher {
+        &mut self.hasher
+    }
 
-        let h1 = self.hash(content.as_slice());
+    /// Prepares the HMAC key of `token` from its decoded secret, done once after decryption and
+    /// again when the PIN of a token was entered.
+    pub(crate) fn prepare(&mut self, token: &mut config::Token) {
+        token.prepare(&mut self.hasher);
+    }
 
-        let mut opad_key = key_padded;
-        for b in opad_key.iter_mut() {
-            *b ^= Self::OPAD;
-        }
+    /// Code of `current` for the unix `timestamp`, `challenge` is only used by challenge-response
+    /// tokens.
+    ///
+    /// Does not allocate, the key has to be prepared with [`Generator::prepare`].
+    pub(crate) fn code(
+        &mut self,
+        current: &config::Token,
+        timestamp: u64,
+        challenge: &str,
+    ) -> Result<Token, algorithm::Error> {
+        current.code(&mut self.hasher, timestamp, challenge)
+    }
 
-        content.clear();
-        content.extend_from_slice(&opad_key);
-        content.extend_from_slice(&h1);
-        let hmac = self.hash(content.as_slice());
+    /// Code of the time based token `current` for the unix `timestamp`.
+    pub(crate) fn token(&mut self, current: &config::Token, timestamp: u64) -> Option<Token> {
+        self.code(current, timestamp, "").ok()
+    }
 
-        let offset = (hmac.last().unwrap() & 0x0F) as usize;
-        Token {
-            code: (u32::from_be_bytes(hmac[offset..=offset + 3].try_into().unwrap()) & 0x7fff_ffff)
-                % 1000000,
-            valid_until: (t + 1) * 30,
+    /// Codes of all `tokens` in one pass, tokens without a time based code (e.g. HOTP or a
+    /// missing PIN) get `None`. Returns the time the first of the codes expires.
+    pub(crate) fn tokens(
+        &mut self,
+        tokens: &[config::Token],
+        timestamp: u64,
+        codes: &mut [Option<Token>],
+    ) -> u64 {
+        let mut valid_until = u64::MAX;
+        for (token, code) in tokens.iter().zip(codes.iter_mut()) {
+            *code = match token.otp().trigger() {
+                _ if token.needs_pin() && token.pin.is_none() => None,
+                Trigger::Time => self.token(token, timestamp),
+                _ => None,
+            };
+            if let Some(code) = code {
+                valid_until = valid_until.min(code.valid_until);
+            }
         }
+        valid_until
     }
 
-    pub(crate) fn timestamp(&self) -> u64 {
-        self.rtc.datetime().and_utc().timestamp() as u64
+    pub(crate) fn timestamp(&self) -> Result<u64, rtc::Error> {
+        Ok(self.datetime()?.and_utc().timestamp() as u64)
     }
 
-    pub(crate) fn datetime(&self) -> NaiveDateTime {
+    pub(crate) fn datetime(&self) -> Result<NaiveDateTime, rtc::Error> {
         self.rtc.datetime()
     }
-
-    fn hash(&mut self, data: &[u8]) -> [u8; 20] {
-        let mut remaining = data;
-        while !remaining.is_empty() {
-            remaining = block!(self.hasher.update(remaining)).unwrap();
-        }
-        let mut output = [0u8; 20];
-        block!(self.hasher.finish(output.as_mut_slice())).unwrap();
-        output
-    }
 }